@@ -1,29 +1,121 @@
 use crate::{cli, shogi, util};
-use rand::{Rng, seq::SliceRandom};
+use rand::{Rng, SeedableRng, seq::SliceRandom};
+use rand_chacha::ChaCha8Rng;
+use std::collections::{HashMap, HashSet};
+use std::fs::{self, File};
+use std::io::{BufRead, BufReader, Seek, SeekFrom};
 
 #[derive(Debug)]
-pub struct OpeningBook {
-    openings: Vec<shogi::Position>,
+enum Openings {
+    Loaded(Vec<shogi::Position>),
+    // An EPD file that is only validated when indexed and read again when a line is played. Holds
+    // the byte offset and line number of every non-empty line.
+    Indexed {
+        file: String,
+        variant: shogi::Variant,
+        lines: Vec<(u64, usize)>,
+    },
+}
+
+impl Openings {
+    fn len(&self) -> usize {
+        match self {
+            Openings::Loaded(openings) => openings.len(),
+            Openings::Indexed { lines, .. } => lines.len(),
+        }
+    }
+}
+
+// The openings of one -openings flag.
+#[derive(Debug)]
+struct Source {
+    openings: Openings,
     current: usize,
+    weight: u32,
+}
+
+#[derive(Debug)]
+pub struct OpeningBook {
+    sources: Vec<Source>,
+    // Index of the source the current opening is drawn from
+    source: usize,
+    // Picks the source of each opening
+    rng: ChaCha8Rng,
 }
 
 impl OpeningBook {
-    pub fn new<R>(options: &cli::BookOptions, rng: &mut R) -> Option<OpeningBook>
+    pub fn new<R>(
+        options: &[cli::BookOptions],
+        variant: shogi::Variant,
+        rules: shogi::GameRules,
+        rng: &mut R,
+    ) -> Option<OpeningBook>
     where
         R: Rng + ?Sized,
     {
-        let Ok(lines) = util::read_lines(&options.file) else {
-            eprintln!("Unable to read file for opening book: {}", options.file);
-            return None;
+        let sources = options
+            .iter()
+            .map(|options| Self::load(options, variant, rules, rng))
+            .collect::<Option<Vec<Source>>>()?;
+        let mut book = OpeningBook {
+            sources,
+            source: 0,
+            rng: ChaCha8Rng::seed_from_u64(rng.random()),
         };
+        book.source = book.pick_source();
+        Some(book)
+    }
 
-        let mut openings = Vec::<shogi::Position>::new();
-        for line in lines.map_while(Result::ok).filter(|l| !l.trim().is_empty()) {
-            let Some(position) = shogi::Position::parse(line.trim()) else {
-                eprintln!("Invalid sfen: {line}");
-                return None;
-            };
-            openings.push(position);
+    fn load<R>(
+        options: &cli::BookOptions,
+        variant: shogi::Variant,
+        rules: shogi::GameRules,
+        rng: &mut R,
+    ) -> Option<Source>
+    where
+        R: Rng + ?Sized,
+    {
+        if options.lazy {
+            let mut lines = Self::index_epd(&options.file, variant)?;
+            if options.random_order {
+                // Same shuffle as for a loaded book, so openings are played in the same order
+                lines.shuffle(rng);
+            }
+            let lines_len = lines.len();
+            return Some(Source {
+                openings: Openings::Indexed {
+                    file: options.file.clone(),
+                    variant,
+                    lines,
+                },
+                current: (options.start_index - 1) % lines_len,
+                weight: options.weight,
+            });
+        }
+
+        let mut openings = match (options.handicap, options.format) {
+            (Some(handicap), _) => vec![shogi::Position::handicap(handicap)],
+            (None, cli::BookFormat::Epd) => Self::read_epd(&options.file)?,
+            (None, cli::BookFormat::Csa) => Self::read_csa(&options.file, options.plies)?,
+            (None, cli::BookFormat::Usi) => {
+                match Self::read_usi(&options.file, options.plies, variant, rules) {
+                    Ok(openings) => openings,
+                    Err(err) => {
+                        eprintln!("{err}");
+                        return None;
+                    }
+                }
+            }
+            (None, cli::BookFormat::YaneDb) => Self::read_yanedb(options, variant, rng)?,
+        };
+
+        if let Some(position) = openings.iter().find(|p| p.variant() != variant) {
+            eprintln!("Opening is not a {} position: {position}", variant.name());
+            return None;
+        }
+
+        if options.mirror || options.flip {
+            openings = Self::augment(openings, options.mirror, options.flip);
         }
 
         if options.random_order {
@@ -32,17 +124,663 @@ impl OpeningBook {
         }
 
         let openings_len = openings.len();
-        Some(OpeningBook {
-            openings,
+        Some(Source {
+            openings: Openings::Loaded(openings),
             current: (options.start_index - 1) % openings_len,
+            weight: options.weight,
         })
     }
 
-    pub fn current(&self) -> shogi::Position {
-        self.openings[self.current]
+    // Picks a source with probability proportional to its weight.
+    fn pick_source(&mut self) -> usize {
+        if self.sources.len() == 1 {
+            return 0;
+        }
+        let total: u32 = self.sources.iter().map(|source| source.weight).sum();
+        let mut pick = self.rng.random_range(0..total);
+        self.sources
+            .iter()
+            .position(|source| {
+                let found = pick < source.weight;
+                pick = pick.saturating_sub(source.weight);
+                found
+            })
+            .unwrap()
+    }
+
+    // Byte offset and line number of every non-empty line. Every line is validated as in a loaded
+    // book, but only its location is kept.
+    fn index_epd(file: &str, variant: shogi::Variant) -> Option<Vec<(u64, usize)>> {
+        let Ok(f) = File::open(file) else {
+            eprintln!("Unable to read file for opening book: {file}");
+            return None;
+        };
+
+        let mut reader = BufReader::new(f);
+        let mut lines = vec![];
+        let mut line = String::new();
+        let mut offset = 0;
+        for line_number in 1.. {
+            line.clear();
+            let Ok(len) = reader.read_line(&mut line) else {
+                eprintln!("Unable to read file for opening book: {file}");
+                return None;
+            };
+            if len == 0 {
+                break;
+            }
+            if !line.trim().is_empty() {
+                Self::parse_indexed_line(&line, &format!("{file}:{line_number}"), variant)?;
+                lines.push((offset, line_number));
+            }
+            offset += len as u64;
+        }
+        if lines.is_empty() {
+            eprintln!("No openings in {file}");
+            return None;
+        }
+        Some(lines)
+    }
+
+    // Parses one line of an EPD book, reporting errors with its location.
+    fn parse_epd_line(line: &str, location: &str) -> Option<shogi::Position> {
+        // Anything after a ';' is an annotation, such as the eval written by evalbook
+        let sfen = line.split(';').next().unwrap().trim();
+        let Some(position) = shogi::Position::parse(sfen) else {
+            eprintln!("{location}: Invalid sfen: {line}");
+            return None;
+        };
+        Self::check_position(&position, location).then_some(position)
+    }
+
+    fn read_indexed(
+        file: &str,
+        variant: shogi::Variant,
+        (offset, line_number): (u64, usize),
+    ) -> Option<shogi::Position> {
+        let mut line = String::new();
+        let read = File::open(file).and_then(|f| {
+            let mut reader = BufReader::new(f);
+            reader.seek(SeekFrom::Start(offset))?;
+            reader.read_line(&mut line)
+        });
+        if read.is_err() {
+            eprintln!("Unable to read file for opening book: {file}");
+            return None;
+        }
+        Self::parse_indexed_line(&line, &format!("{file}:{line_number}"), variant)
+    }
+
+    // Parses a line of a lazily loaded book, which must hold a position of `variant`.
+    fn parse_indexed_line(
+        line: &str,
+        location: &str,
+        variant: shogi::Variant,
+    ) -> Option<shogi::Position> {
+        let position = Self::parse_epd_line(line, location)?;
+        if position.variant() != variant {
+            eprintln!(
+                "{location}: Opening is not a {} position: {position}",
+                variant.name()
+            );
+            return None;
+        }
+        Some(position)
+    }
+
+    pub fn read_epd(file: &str) -> Option<Vec<shogi::Position>> {
+        let Ok(lines) = util::read_lines(file) else {
+            eprintln!("Unable to read file for opening book: {file}");
+            return None;
+        };
+
+        let mut openings = Vec::<shogi::Position>::new();
+        for (i, line) in lines
+            .map_while(Result::ok)
+            .enumerate()
+            .filter(|(_, l)| !l.trim().is_empty())
+        {
+            openings.push(Self::parse_epd_line(&line, &format!("{file}:{}", i + 1))?);
+        }
+        Some(openings)
+    }
+
+    // Each line is a USI position string such as "startpos moves 7g7f 3c3d", contributing the
+    // position after at most `plies` of its moves. The moves are replayed under the game rules of
+    // the tournament, so a line that already ends by repetition there is rejected. The error names
+    // the file and line of the first invalid line.
+    fn read_usi(
+        file: &str,
+        plies: Option<usize>,
+        variant: shogi::Variant,
+        rules: shogi::GameRules,
+    ) -> Result<Vec<shogi::Position>, String> {
+        let lines = util::read_lines(file)
+            .map_err(|_| format!("Unable to read file for opening book: {file}"))?;
+
+        let mut openings = Vec::<shogi::Position>::new();
+        for (i, line) in lines
+            .map_while(Result::ok)
+            .enumerate()
+            .filter(|(_, l)| !l.trim().is_empty())
+        {
+            let location = format!("{file}:{}", i + 1);
+            let game = shogi::Game::from_usi(&line, variant, rules)
+                .map_err(|err| format!("{location}: Invalid opening line: {err}"))?;
+            let history = game.history();
+            let position = history[plies.unwrap_or(usize::MAX).min(history.len() - 1)];
+            if let Some(violation) = position.validate().first() {
+                return Err(format!(
+                    "{location}: Invalid opening {position}: {violation}"
+                ));
+            }
+            openings.push(position);
+        }
+        Ok(openings)
+    }
+
+    // Draws `options.count` lines from a YaneuraOu book, each walked from the starting position.
+    fn read_yanedb<R>(
+        options: &cli::BookOptions,
+        variant: shogi::Variant,
+        rng: &mut R,
+    ) -> Option<Vec<shogi::Position>>
+    where
+        R: Rng + ?Sized,
+    {
+        let file = &options.file;
+        let Ok(text) = fs::read_to_string(file) else {
+            eprintln!("Unable to read file for opening book: {file}");
+            return None;
+        };
+        let book = match YaneBook::parse(&text) {
+            Ok(book) => book,
+            Err(err) => {
+                eprintln!("{file}:{err}");
+                return None;
+            }
+        };
+
+        let startpos = variant.startpos();
+        if !book.entries.contains_key(&startpos.key()) {
+            eprintln!("{file}: No book moves for the starting position");
+            return None;
+        }
+        let plies = options.plies.unwrap_or(usize::MAX);
+        Some(
+            (0..options.count)
+                .map(|_| book.walk(startpos, plies, options.eval_window, rng))
+                .collect(),
+        )
+    }
+
+    // Each record contributes the position after `plies` of its moves, or after all of them if
+    // `plies` is None. Records that end earlier are finished games and are skipped.
+    fn read_csa(file: &str, plies: Option<usize>) -> Option<Vec<shogi::Position>> {
+        let Ok(text) = fs::read_to_string(file) else {
+            eprintln!("Unable to read file for opening book: {file}");
+            return None;
+        };
+
+        let records = match shogi::CsaRecord::parse_all(&text) {
+            Ok(records) => records,
+            Err(err) => {
+                eprintln!("Invalid CSA record in {file}: {err}");
+                return None;
+            }
+        };
+
+        let mut openings = vec![];
+        for (i, record) in records.iter().enumerate() {
+            let location = format!("{file}: record {}", i + 1);
+            let positions = match record.positions() {
+                Ok(positions) => positions,
+                Err(err) => {
+                    eprintln!("{location}: {err}");
+                    return None;
+                }
+            };
+            let Some(&position) = positions.get(plies.unwrap_or(positions.len() - 1)) else {
+                continue;
+            };
+            if !Self::check_position(&position, &location) {
+                return None;
+            }
+            openings.push(position);
+        }
+        if openings.len() < records.len() {
+            eprintln!(
+                "Skipped {} of {} records in {file} with fewer than {} moves",
+                records.len() - openings.len(),
+                records.len(),
+                plies.unwrap_or_default()
+            );
+        }
+        Some(openings)
+    }
+
+    // Adds the mirrored and colour-flipped copies of each opening right after it. Copies that are
+    // already in the book, such as the mirror image of a symmetric position, are left out.
+    fn augment(openings: Vec<shogi::Position>, mirror: bool, flip: bool) -> Vec<shogi::Position> {
+        let mut seen: HashSet<u64> = openings.iter().map(|p| p.key()).collect();
+        let mut augmented = Vec::with_capacity(openings.len() * 4);
+        for position in openings {
+            let mut copies = vec![];
+            if mirror {
+                copies.push(position.mirror());
+            }
+            if flip {
+                copies.push(position.flip_colors());
+            }
+            if mirror && flip {
+                copies.push(position.mirror().flip_colors());
+            }
+            augmented.push(position);
+            augmented.extend(copies.into_iter().filter(|p| seen.insert(p.key())));
+        }
+        augmented
+    }
+
+    // Reports every rule violation in `position`, which is found at `location`.
+    fn check_position(position: &shogi::Position, location: &str) -> bool {
+        let violations = position.validate();
+        for violation in &violations {
+            eprintln!("{location}: Invalid opening {position}: {violation}");
+        }
+        violations.is_empty()
+    }
+
+    /// The opening to play next. Returns None if a lazily loaded book can no longer be read.
+    pub fn current(&mut self) -> Option<shogi::Position> {
+        let source = &mut self.sources[self.source];
+        match &mut source.openings {
+            Openings::Loaded(openings) => Some(openings[source.current]),
+            Openings::Indexed {
+                file,
+                variant,
+                lines,
+            } => Self::read_indexed(file, *variant, lines[source.current]),
+        }
+    }
+
+    /// Index of the `-openings` source that `current` is drawn from.
+    pub fn source(&self) -> usize {
+        self.source
     }
 
     pub fn advance(&mut self) {
-        self.current = (self.current + 1) % self.openings.len();
+        let source = &mut self.sources[self.source];
+        source.current = (source.current + 1) % source.openings.len();
+        self.source = self.pick_source();
+    }
+}
+
+#[derive(Debug, Clone, Copy)]
+struct YaneBookMove {
+    m: shogi::Move,
+    eval: i32,
+    count: u64,
+}
+
+// A book in YaneuraOu's YANEURAOU-DB2016 text format: each "sfen SFEN" line is followed by one
+// line per candidate move, "MOVE PONDER EVAL DEPTH [COUNT]", with the eval from the point of view
+// of the side to move.
+#[derive(Debug, Default)]
+struct YaneBook {
+    // Position key -> candidate moves
+    entries: HashMap<u64, Vec<YaneBookMove>>,
+}
+
+impl YaneBook {
+    // Errors are prefixed with the line number.
+    fn parse(text: &str) -> Result<YaneBook, String> {
+        let mut book = YaneBook::default();
+        let mut position = None;
+        for (i, line) in text.lines().enumerate() {
+            let line = line.trim();
+            if line.is_empty() || line.starts_with('#') || line.starts_with("//") {
+                continue;
+            }
+            if let Some(sfen) = line.strip_prefix("sfen ") {
+                let Some(p) = shogi::Position::parse(sfen.trim()) else {
+                    return Err(format!("{}: Invalid sfen: {sfen}", i + 1));
+                };
+                book.entries.entry(p.key()).or_default();
+                position = Some(p);
+                continue;
+            }
+
+            let Some(position) = position else {
+                return Err(format!("{}: Book move before the first sfen", i + 1));
+            };
+            let fields: Vec<&str> = line.split_whitespace().collect();
+            let book_move = match fields[..] {
+                [m, _ponder, eval, _depth, ref rest @ ..] if rest.len() <= 1 => {
+                    let m = shogi::Move::parse(m).filter(|&m| position.is_legal(m));
+                    let eval = eval.parse().ok();
+                    let count = rest.first().map_or(Some(1), |count| count.parse().ok());
+                    m.zip(eval)
+                        .zip(count)
+                        .map(|((m, eval), count)| YaneBookMove { m, eval, count })
+                }
+                _ => None,
+            };
+            let Some(book_move) = book_move else {
+                return Err(format!("{}: Invalid book move: {line}", i + 1));
+            };
+            book.entries
+                .get_mut(&position.key())
+                .unwrap()
+                .push(book_move);
+        }
+        Ok(book)
+    }
+
+    // Plays book moves from `position` until `plies` moves have been played or the book has no
+    // move within `eval_window` of zero. Moves are picked with probability proportional to their
+    // count, or uniformly if none of them has been played.
+    fn walk<R>(
+        &self,
+        mut position: shogi::Position,
+        plies: usize,
+        eval_window: Option<i32>,
+        rng: &mut R,
+    ) -> shogi::Position
+    where
+        R: Rng + ?Sized,
+    {
+        for _ in 0..plies {
+            let Some(moves) = self.entries.get(&position.key()) else {
+                break;
+            };
+            let candidates: Vec<&YaneBookMove> = moves
+                .iter()
+                .filter(|bm| eval_window.is_none_or(|window| bm.eval.abs() <= window))
+                .collect();
+            if candidates.is_empty() {
+                break;
+            }
+
+            let total: u64 = candidates.iter().map(|bm| bm.count).sum();
+            let chosen = if total == 0 {
+                candidates[rng.random_range(0..candidates.len())]
+            } else {
+                let mut pick = rng.random_range(0..total);
+                *candidates
+                    .iter()
+                    .find(|bm| {
+                        let found = pick < bm.count;
+                        pick = pick.saturating_sub(bm.count);
+                        found
+                    })
+                    .unwrap()
+            };
+            position = position.do_move(chosen.m).unwrap();
+        }
+        position
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn augment_skips_duplicates() {
+        let parse = |sfen| shogi::Position::parse(sfen).unwrap();
+        let opening = parse("lnsgkgsnl/1r5b1/ppppppppp/9/9/2P6/PP1PPPPPP/1B5R1/LNSGKGSNL w - 2");
+        let symmetric = parse("4k4/9/9/9/9/9/9/9/4K4 b G 1");
+
+        let augment = |openings: &[shogi::Position], mirror, flip| {
+            OpeningBook::augment(openings.to_vec(), mirror, flip)
+        };
+        assert_eq!(3, augment(&[opening, symmetric], true, false).len());
+        assert_eq!(4, augment(&[opening, symmetric], false, true).len());
+        assert_eq!(6, augment(&[opening, symmetric], true, true).len());
+        assert_eq!(
+            vec![opening, opening.flip_colors()],
+            augment(&[opening, opening.flip_colors()], false, true)
+        );
+    }
+
+    #[test]
+    fn weighted_sources() {
+        let source = |sfens: &[&str], weight| Source {
+            openings: Openings::Loaded(
+                sfens
+                    .iter()
+                    .map(|sfen| shogi::Position::parse(sfen).unwrap())
+                    .collect(),
+            ),
+            current: 0,
+            weight,
+        };
+        let first = ["lnsgkgsnl/1r5b1/ppppppppp/9/9/9/PPPPPPPPP/1B5R1/LNSGKGSNL b - 1"];
+        let second = [
+            "lnsgkgsnl/1r5b1/ppppppppp/9/9/2P6/PP1PPPPPP/1B5R1/LNSGKGSNL w - 2",
+            "lnsgkgsnl/1r5b1/ppppppppp/9/9/7P1/PPPPPPP1P/1B5R1/LNSGKGSNL w - 2",
+        ];
+        let mut book = OpeningBook {
+            sources: vec![source(&first, 1), source(&second, 3)],
+            source: 0,
+            rng: ChaCha8Rng::seed_from_u64(3),
+        };
+
+        let mut counts = [0; 2];
+        let mut second_seen = vec![];
+        for _ in 0..4000 {
+            counts[book.source()] += 1;
+            if book.source() == 1 {
+                second_seen.push(book.current().unwrap());
+            }
+            book.advance();
+        }
+        assert!((800..1200).contains(&counts[0]), "{counts:?}");
+        // Each source still plays its own openings in order
+        assert!(
+            second_seen
+                .chunks(2)
+                .all(|pair| pair[0].to_string() == second[0])
+        );
+    }
+
+    #[test]
+    fn csa_book_plies() {
+        let file = std::env::temp_dir().join(format!("shogitest-{}.csa", std::process::id()));
+        let file_name = file.to_string_lossy().to_string();
+        fs::write(
+            &file,
+            "PI\n+\n+7776FU\n-3334FU\n+8822UM\n-3122GI\n%TORYO\n/\nPI\n+\n+2726FU\n%TORYO\n",
+        )
+        .unwrap();
+
+        let after_7g7f_3c3d = "lnsgkgsnl/1r5b1/pppppp1pp/6p2/9/2P6/PP1PPPPPP/1B5R1/LNSGKGSNL b - 3";
+        let openings = OpeningBook::read_csa(&file_name, Some(2)).unwrap();
+        assert_eq!(
+            vec![after_7g7f_3c3d.to_string()],
+            openings.iter().map(|p| p.to_string()).collect::<Vec<_>>()
+        );
+        assert_eq!(2, OpeningBook::read_csa(&file_name, Some(1)).unwrap().len());
+        assert_eq!(2, OpeningBook::read_csa(&file_name, None).unwrap().len());
+        fs::remove_file(&file).unwrap();
+    }
+
+    #[test]
+    fn usi_book() {
+        let file = std::env::temp_dir().join(format!("shogitest-{}.usi", std::process::id()));
+        let file_name = file.to_string_lossy().to_string();
+        let rules = shogi::GameRules::default();
+        let read = |plies, variant, rules| {
+            OpeningBook::read_usi(&file_name, plies, variant, rules)
+                .map(|openings| openings.iter().map(|p| p.to_string()).collect::<Vec<_>>())
+        };
+
+        let kings = "4k4/9/9/9/9/9/9/9/4K4 w G 1";
+        fs::write(
+            &file,
+            format!("startpos moves 7g7f 3c3d 2g2f\n\nposition sfen {kings} moves\n"),
+        )
+        .unwrap();
+        let after_7g7f_3c3d = "lnsgkgsnl/1r5b1/pppppp1pp/6p2/9/2P6/PP1PPPPPP/1B5R1/LNSGKGSNL b - 3";
+        let after_2g2f = "lnsgkgsnl/1r5b1/pppppp1pp/6p2/9/2P4P1/PP1PPPP1P/1B5R1/LNSGKGSNL w - 4";
+        let standard = shogi::Variant::Standard;
+        assert_eq!(
+            Ok(vec![after_7g7f_3c3d.to_string(), kings.to_string()]),
+            read(Some(2), standard, rules)
+        );
+        // Lines shorter than `plies` contribute their final position
+        assert_eq!(
+            Ok(vec![after_2g2f.to_string(), kings.to_string()]),
+            read(Some(10), standard, rules)
+        );
+        assert_eq!(read(Some(10), standard, rules), read(None, standard, rules));
+
+        fs::write(&file, "startpos moves 7g7f\n\nstartpos moves 7g7f 7g7f\n").unwrap();
+        assert_eq!(
+            Err(format!(
+                "{file_name}:3: Invalid opening line: Move 2 (7g7f) is illegal"
+            )),
+            read(None, standard, rules)
+        );
+
+        // The line repeats a position three times, which only ends the game under repetitions=3
+        let moves = "2h7h 8b9b 7h6h 9b8b 6h7h 8b9b 7h6h 9b8b 6h7h 8b9b 7h6h";
+        fs::write(&file, format!("startpos moves {moves}\n")).unwrap();
+        assert!(read(None, standard, rules).is_ok());
+        let three_fold = shogi::GameRules {
+            repetitions: 3,
+            ..rules
+        };
+        assert_eq!(
+            Err(format!(
+                "{file_name}:1: Invalid opening line: Move 10 (8b9b) is played after the game ended"
+            )),
+            read(None, standard, three_fold)
+        );
+
+        // startpos is the starting position of the variant
+        fs::write(&file, "startpos moves 5d5c\n").unwrap();
+        assert_eq!(
+            Ok(vec!["rbsgk/4p/P4/5/KGSBR w - 2".to_string()]),
+            read(None, shogi::Variant::Minishogi, rules)
+        );
+        fs::remove_file(&file).unwrap();
+    }
+
+    #[test]
+    fn lazy_book_matches_loaded_book() {
+        let file = std::env::temp_dir().join(format!("shogitest-lazy-{}.epd", std::process::id()));
+        let sfens = [
+            "lnsgkgsnl/1r5b1/ppppppppp/9/9/9/PPPPPPPPP/1B5R1/LNSGKGSNL b - 1",
+            "lnsgkgsnl/1r5b1/ppppppppp/9/9/2P6/PP1PPPPPP/1B5R1/LNSGKGSNL w - 2",
+            "lnsgkgsnl/1r5b1/pppppp1pp/6p2/9/2P6/PP1PPPPPP/1B5R1/LNSGKGSNL b - 3; eval 20",
+            "lnsgkgsnl/1r5b1/ppppppppp/9/9/7P1/PPPPPPP1P/1B5R1/LNSGKGSNL w - 2",
+            "lnsgkgsnl/1r5b1/p1ppppppp/1p7/9/7P1/PPPPPPP1P/1B5R1/LNSGKGSNL b - 3",
+        ];
+        fs::write(
+            &file,
+            format!("{}\n\n{}\r\n", sfens[..3].join("\n"), sfens[3..].join("\n")),
+        )
+        .unwrap();
+
+        for random_order in [false, true] {
+            let options = cli::BookOptions {
+                file: file.to_string_lossy().to_string(),
+                random_order,
+                start_index: 4,
+                ..cli::BookOptions::default()
+            };
+            let lazy_options = cli::BookOptions {
+                lazy: true,
+                ..options.clone()
+            };
+            let variant = shogi::Variant::Standard;
+            let mut rng = rand_chacha::ChaCha8Rng::seed_from_u64(7);
+            let mut loaded =
+                OpeningBook::new(&[options], variant, shogi::GameRules::default(), &mut rng)
+                    .unwrap();
+            let mut rng = rand_chacha::ChaCha8Rng::seed_from_u64(7);
+            let mut lazy = OpeningBook::new(
+                &[lazy_options],
+                variant,
+                shogi::GameRules::default(),
+                &mut rng,
+            )
+            .unwrap();
+
+            for _ in 0..7 {
+                assert_eq!(loaded.current().unwrap(), lazy.current().unwrap());
+                loaded.advance();
+                lazy.advance();
+            }
+        }
+        fs::remove_file(&file).unwrap();
+    }
+
+    #[test]
+    fn lazy_book_rejects_invalid_lines() {
+        let file =
+            std::env::temp_dir().join(format!("shogitest-corrupt-{}.epd", std::process::id()));
+        let sfen = "lnsgkgsnl/1r5b1/ppppppppp/9/9/9/PPPPPPPPP/1B5R1/LNSGKGSNL b - 1";
+        let options = cli::BookOptions {
+            file: file.to_string_lossy().to_string(),
+            lazy: true,
+            ..cli::BookOptions::default()
+        };
+        let mut rng = rand_chacha::ChaCha8Rng::seed_from_u64(7);
+        for (text, valid) in [
+            (format!("{sfen}\n"), true),
+            (format!("{sfen}\nnot a position\n"), false),
+            (format!("{sfen}\n4k4/9/9/9/9/9/9/9/9 b - 1\n"), false),
+            (String::new(), false),
+            (String::from(" \n\r\n"), false),
+        ] {
+            fs::write(&file, &text).unwrap();
+            let book = OpeningBook::new(
+                std::slice::from_ref(&options),
+                shogi::Variant::Standard,
+                shogi::GameRules::default(),
+                &mut rng,
+            );
+            assert_eq!(valid, book.is_some(), "{text:?}");
+        }
+        fs::remove_file(&file).unwrap();
+    }
+
+    #[test]
+    fn yanedb_walk() {
+        let book = YaneBook::parse(
+            "#YANEURAOU-DB2016 1.00
+sfen lnsgkgsnl/1r5b1/ppppppppp/9/9/9/PPPPPPPPP/1B5R1/LNSGKGSNL b - 1
+7g7f 3c3d 30 20 3
+2g2f 8c8d 40 20 1
+5i5h none -300 10 0
+sfen lnsgkgsnl/1r5b1/ppppppppp/9/9/2P6/PP1PPPPPP/1B5R1/LNSGKGSNL w - 2
+3c3d none -20 20 5
+sfen lnsgkgsnl/1r5b1/ppppppppp/9/9/7P1/PPPPPPP1P/1B5R1/LNSGKGSNL w - 2
+8c8d 7g7f -30 20
+",
+        )
+        .unwrap();
+        let startpos = shogi::Position::default();
+        let mut rng = rand_chacha::ChaCha8Rng::seed_from_u64(0);
+
+        let mut counts = HashMap::new();
+        for _ in 0..400 {
+            let position = book.walk(startpos, 2, Some(100), &mut rng);
+            assert_eq!(3, position.ply());
+            *counts.entry(position.to_string()).or_insert(0) += 1;
+        }
+        let after_7g7f = "lnsgkgsnl/1r5b1/pppppp1pp/6p2/9/2P6/PP1PPPPPP/1B5R1/LNSGKGSNL b - 3";
+        let after_2g2f = "lnsgkgsnl/1r5b1/p1ppppppp/1p7/9/7P1/PPPPPPP1P/1B5R1/LNSGKGSNL b - 3";
+        assert_eq!(2, counts.len());
+        assert!(counts[after_7g7f] > 2 * counts[after_2g2f]);
+
+        // 5i5h falls outside the eval window, and the line stops where the book ends
+        let position = book.walk(startpos, 10, Some(35), &mut rng);
+        assert_eq!(after_7g7f, position.to_string());
+
+        assert!(YaneBook::parse("7g7f 3c3d 0 0 1").is_err());
+        assert!(YaneBook::parse("sfen 4k4/9/9/9/9/9/9/9/4K4 b - 1\n7g7f none 0 0 1").is_err());
     }
 }
@@ -1,6 +1,7 @@
 use std::time::Duration;
 
 use crate::engine;
+use crate::shogi;
 use crate::tc;
 
 #[derive(Debug, Clone)]
@@ -14,6 +15,23 @@ pub struct BookOptions {
     pub file: String,
     pub random_order: bool,
     pub start_index: usize,
+    pub format: BookFormat,
+    pub handicap: Option<shogi::Handicap>,
+    pub mirror: bool,
+    pub flip: bool,
+    pub plies: Option<usize>,
+    pub eval_window: Option<i32>,
+    pub count: usize,
+    pub lazy: bool,
+    pub weight: u32,
+}
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum BookFormat {
+    Epd,
+    Csa,
+    Usi,
+    YaneDb,
 }
 
 impl Default for BookOptions {
@@ -22,6 +40,15 @@ impl Default for BookOptions {
             file: String::from("<none>"),
             random_order: false,
             start_index: 1,
+            format: BookFormat::Epd,
+            handicap: None,
+            mirror: false,
+            flip: false,
+            plies: None,
+            eval_window: None,
+            count: 1000,
+            lazy: false,
+            weight: 1,
         }
     }
 }
@@ -31,6 +58,7 @@ pub struct AdjudicationOptions {
     pub max_moves: Option<u64>,
     pub draw: Option<DrawAdjudicationOptions>,
     pub resign: Option<ResignAdjudicationOptions>,
+    pub mate: Option<MateAdjudicationOptions>,
 }
 
 impl Default for AdjudicationOptions {
@@ -39,6 +67,7 @@ impl Default for AdjudicationOptions {
             max_moves: Some(512),
             draw: None,
             resign: None,
+            mate: None,
         }
     }
 }
@@ -77,6 +106,21 @@ impl Default for ResignAdjudicationOptions {
     }
 }
 
+#[derive(Debug, Clone)]
+pub struct MateAdjudicationOptions {
+    pub plies: usize,
+    pub nodes: u64,
+}
+
+impl Default for MateAdjudicationOptions {
+    fn default() -> Self {
+        MateAdjudicationOptions {
+            plies: 1,
+            nodes: 100000,
+        }
+    }
+}
+
 #[derive(Debug, Clone)]
 pub struct SprtOptions {
     pub nelo0: f64,
@@ -99,23 +143,36 @@ impl Default for SprtOptions {
 #[derive(Debug, Clone)]
 pub struct CliOptions {
     pub engines: Vec<EngineOptions>,
-    pub book: Option<BookOptions>,
+    pub books: Vec<BookOptions>,
     pub games: Option<u64>,
     pub rounds: u64,
     pub concurrency: u64,
     pub rand_seed: Option<u64>,
     pub meta: MetaDataOptions,
     pub pgn: Option<PgnOutOptions>,
+    pub kif: Option<KifOutOptions>,
+    pub csa: Option<CsaOutOptions>,
     pub adjudication: AdjudicationOptions,
+    pub rules: shogi::GameRules,
+    pub variant: shogi::Variant,
     pub report_interval: Option<u64>,
     pub sprt: Option<SprtOptions>,
+    pub list_options: bool,
 }
 
 impl CliOptions {
-    pub fn engine_names(&self) -> Vec<String> {
+    /// Starts every engine once to learn its name. Returns None, after reporting the error, if
+    /// an engine fails to start or rejects its configured options.
+    pub fn engine_names(&self) -> Option<Vec<String>> {
         self.engines
             .iter()
-            .map(|e| e.builder.init().unwrap().name().to_string())
+            .map(|e| match e.builder.init() {
+                Ok(engine) => Some(engine.name().to_string()),
+                Err(err) => {
+                    eprintln!("{err}");
+                    None
+                }
+            })
             .collect()
     }
 }
@@ -124,7 +181,7 @@ impl Default for CliOptions {
     fn default() -> Self {
         CliOptions {
             engines: vec![],
-            book: None,
+            books: vec![],
             games: None,
             rounds: 2,
             concurrency: 1,
@@ -134,9 +191,14 @@ impl Default for CliOptions {
                 site_name: String::from("?"),
             },
             pgn: None,
+            kif: None,
+            csa: None,
             adjudication: AdjudicationOptions::default(),
+            rules: shogi::GameRules::default(),
+            variant: shogi::Variant::default(),
             report_interval: Some(10),
             sprt: None,
+            list_options: false,
         }
     }
 }
@@ -147,11 +209,21 @@ pub struct EngineOptions {
     pub time_control: tc::TimeControl,
     pub time_margin: Duration,
     pub restart: bool,
+    pub ponder: bool,
+}
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Default)]
+pub enum MoveNotation {
+    #[default]
+    Usi,
+    Western,
+    Japanese,
 }
 
 #[derive(Debug, Clone)]
 pub struct PgnOutOptions {
     pub file: String,
+    pub notation: MoveNotation,
     pub track_nodes: bool,
     pub track_seldepth: bool,
     pub track_nps: bool,
@@ -164,6 +236,7 @@ impl Default for PgnOutOptions {
     fn default() -> Self {
         PgnOutOptions {
             file: String::default(),
+            notation: MoveNotation::Usi,
             track_nodes: true,
             track_seldepth: true,
             track_nps: false,
@@ -174,6 +247,123 @@ impl Default for PgnOutOptions {
     }
 }
 
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Default)]
+pub enum KifFormat {
+    #[default]
+    Kif,
+    Ki2,
+}
+
+#[derive(Debug, Clone, Default)]
+pub struct KifOutOptions {
+    pub file: String,
+    pub format: KifFormat,
+}
+
+#[derive(Debug, Clone, Default)]
+pub struct CsaOutOptions {
+    pub file: String,
+}
+
+#[derive(Debug, Clone)]
+pub struct PerftOptions {
+    pub position: shogi::Position,
+    pub depth: usize,
+    pub divide: bool,
+    pub threads: usize,
+    pub engine: Option<engine::EngineBuilder>,
+}
+
+impl Default for PerftOptions {
+    fn default() -> Self {
+        PerftOptions {
+            position: shogi::Position::default(),
+            depth: 1,
+            divide: false,
+            threads: 1,
+            engine: None,
+        }
+    }
+}
+
+#[derive(Debug, Clone)]
+pub struct GenbookOptions {
+    pub out: Option<String>,
+    pub book: Option<String>,
+    pub count: usize,
+    pub plies: usize,
+    pub no_check: bool,
+    pub no_captures: bool,
+    pub max_material: Option<i32>,
+    pub seed: Option<u64>,
+}
+
+impl Default for GenbookOptions {
+    fn default() -> Self {
+        GenbookOptions {
+            out: None,
+            book: None,
+            count: 100,
+            plies: 8,
+            no_check: false,
+            no_captures: false,
+            max_material: None,
+            seed: None,
+        }
+    }
+}
+
+#[derive(Debug, Clone)]
+pub struct EvalBookOptions {
+    pub file: String,
+    pub out: Option<String>,
+    pub engine: engine::EngineBuilder,
+    pub nodes: u64,
+    pub movetime: Option<Duration>,
+    // How long a `go nodes` search may take before the engine is given up on
+    pub timeout: Duration,
+    pub window: Option<i32>,
+    pub buckets: Vec<i32>,
+    pub annotate: bool,
+}
+
+impl Default for EvalBookOptions {
+    fn default() -> Self {
+        EvalBookOptions {
+            file: String::from("<none>"),
+            out: None,
+            engine: engine::EngineBuilder::default(),
+            nodes: 100000,
+            movetime: None,
+            timeout: 60 * Duration::SECOND,
+            window: None,
+            buckets: vec![],
+            annotate: false,
+        }
+    }
+}
+
+#[derive(Debug, Clone)]
+pub struct GameBookOptions {
+    pub dir: String,
+    pub out: Option<String>,
+    pub ply: usize,
+    pub min_count: usize,
+    pub balanced: Option<i32>,
+}
+
+impl Default for GameBookOptions {
+    fn default() -> Self {
+        GameBookOptions {
+            dir: String::from("<none>"),
+            out: None,
+            ply: 16,
+            min_count: 1,
+            balanced: None,
+        }
+    }
+}
+
 fn parse_engine_option(engine: &mut EngineOptions, name: &str, value: &str) -> bool {
     match name {
         "name" => {
@@ -237,6 +427,14 @@ fn parse_engine_option(engine: &mut EngineOptions, name: &str, value: &str) -> b
                 return false;
             }
         },
+        "ponder" => match value {
+            "on" => engine.ponder = true,
+            "off" => engine.ponder = false,
+            _ => {
+                eprintln!("Invalid value {value} for engine ponder option");
+                return false;
+            }
+        },
         "proto" => match value {
             "usi" => {}
             _ => {
@@ -258,6 +456,274 @@ fn parse_engine_option(engine: &mut EngineOptions, name: &str, value: &str) -> b
     true
 }
 
+/// Parses the arguments of the `perft` subcommand.
+pub fn parse_perft() -> Option<PerftOptions> {
+    let args: Vec<String> = std::env::args().skip(2).collect();
+
+    let mut options = PerftOptions::default();
+    for arg in &args {
+        let (name, value) = arg.split_once('=').unwrap_or((arg, ""));
+        match name {
+            "sfen" => {
+                let Some(position) = shogi::Position::parse(value.trim()) else {
+                    eprintln!("Invalid sfen {value} for perft");
+                    return None;
+                };
+                if let Some(violation) = position.validate().first() {
+                    eprintln!("Invalid sfen {value} for perft: {violation}");
+                    return None;
+                }
+                options.position = position;
+            }
+            "depth" => {
+                options.depth = match value.parse::<usize>() {
+                    Ok(value) if value > 0 => value,
+                    _ => {
+                        eprintln!("Invalid depth {value} for perft");
+                        return None;
+                    }
+                };
+            }
+            "divide" => {
+                options.divide = true;
+            }
+            "threads" => {
+                options.threads = match value.parse::<usize>() {
+                    Ok(value) if value > 0 => value,
+                    _ => {
+                        eprintln!("Invalid threads {value} for perft");
+                        return None;
+                    }
+                };
+            }
+            "engine" => {
+                options.engine = Some(engine::EngineBuilder {
+                    cmd: String::from(value),
+                    ..engine::EngineBuilder::default()
+                });
+            }
+            _ => {
+                eprintln!("Unrecognised perft option {arg}");
+                return None;
+            }
+        }
+    }
+
+    if let Some(builder) = &mut options.engine
+        && let Some(usi_variant) = options.position.variant().usi_variant()
+    {
+        builder
+            .usi_options
+            .push((String::from("USI_Variant"), String::from(usi_variant)));
+    }
+
+    Some(options)
+}
+
+pub fn parse_genbook() -> Option<GenbookOptions> {
+    let args: Vec<String> = std::env::args().skip(2).collect();
+
+    let mut options = GenbookOptions::default();
+    for arg in &args {
+        let (name, value) = arg.split_once('=').unwrap_or((arg, ""));
+        match name {
+            "out" => {
+                options.out = Some(String::from(value));
+            }
+            "book" => {
+                options.book = Some(String::from(value));
+            }
+            "count" => {
+                options.count = match value.parse::<usize>() {
+                    Ok(value) if value > 0 => value,
+                    _ => {
+                        eprintln!("Invalid count {value} for genbook");
+                        return None;
+                    }
+                };
+            }
+            "plies" => {
+                let Ok(value) = value.parse::<usize>() else {
+                    eprintln!("Invalid plies {value} for genbook");
+                    return None;
+                };
+                options.plies = value;
+            }
+            "nocheck" => {
+                options.no_check = true;
+            }
+            "nocaptures" => {
+                options.no_captures = true;
+            }
+            "material" => {
+                let Ok(value) = value.parse::<i32>() else {
+                    eprintln!("Invalid material {value} for genbook");
+                    return None;
+                };
+                options.max_material = Some(value.abs());
+            }
+            "seed" => {
+                let Ok(value) = value.parse::<u64>() else {
+                    eprintln!("Invalid seed {value} for genbook");
+                    return None;
+                };
+                options.seed = Some(value);
+            }
+            _ => {
+                eprintln!("Unrecognised genbook option {arg}");
+                return None;
+            }
+        }
+    }
+
+    Some(options)
+}
+
+pub fn parse_evalbook() -> Option<EvalBookOptions> {
+    let args: Vec<String> = std::env::args().skip(2).collect();
+
+    let mut options = EvalBookOptions::default();
+    for arg in &args {
+        let (name, value) = arg.split_once('=').unwrap_or((arg, ""));
+        match name {
+            "file" => {
+                options.file = String::from(value);
+            }
+            "out" => {
+                options.out = Some(String::from(value));
+            }
+            "engine" => {
+                options.engine.cmd = String::from(value);
+            }
+            "dir" => {
+                options.engine.dir = String::from(value);
+            }
+            "nodes" => {
+                options.nodes = match value.parse::<u64>() {
+                    Ok(value) if value > 0 => value,
+                    _ => {
+                        eprintln!("Invalid nodes {value} for evalbook");
+                        return None;
+                    }
+                };
+            }
+            "movetime" => {
+                let Ok(value) = value.parse::<u64>() else {
+                    eprintln!("Invalid movetime {value} for evalbook");
+                    return None;
+                };
+                options.movetime = Some(Duration::from_millis(value));
+            }
+            "timeout" => {
+                options.timeout = match value.parse::<u64>() {
+                    Ok(value) if value > 0 => Duration::from_secs(value),
+                    _ => {
+                        eprintln!("Invalid timeout {value} for evalbook");
+                        return None;
+                    }
+                };
+            }
+            "window" => {
+                let Ok(value) = value.parse::<i32>() else {
+                    eprintln!("Invalid window {value} for evalbook");
+                    return None;
+                };
+                options.window = Some(value.abs());
+            }
+            "buckets" => {
+                let Ok(buckets) = value
+                    .split(',')
+                    .map(|limit| limit.parse::<i32>())
+                    .collect::<Result<Vec<i32>, _>>()
+                else {
+                    eprintln!("Invalid buckets {value} for evalbook");
+                    return None;
+                };
+                if !buckets.is_sorted() {
+                    eprintln!("Evalbook buckets must be in increasing order");
+                    return None;
+                }
+                options.buckets = buckets;
+            }
+            "annotate" => {
+                options.annotate = true;
+            }
+            name if let Some(optionname) = name.strip_prefix("option.") => {
+                options
+                    .engine
+                    .usi_options
+                    .push((String::from(optionname), String::from(value)));
+            }
+            _ => {
+                eprintln!("Unrecognised evalbook option {arg}");
+                return None;
+            }
+        }
+    }
+
+    if options.file == EvalBookOptions::default().file || options.engine.cmd.is_empty() {
+        eprintln!("Evalbook requires file= and engine=");
+        return None;
+    }
+    if !options.buckets.is_empty() && options.out.is_none() {
+        eprintln!("Evalbook buckets option requires out=");
+        return None;
+    }
+
+    Some(options)
+}
+
+pub fn parse_gamebook() -> Option<GameBookOptions> {
+    let args: Vec<String> = std::env::args().skip(2).collect();
+
+    let mut options = GameBookOptions::default();
+    for arg in &args {
+        let (name, value) = arg.split_once('=').unwrap_or((arg, ""));
+        match name {
+            "dir" => {
+                options.dir = String::from(value);
+            }
+            "out" => {
+                options.out = Some(String::from(value));
+            }
+            "ply" => {
+                let Ok(value) = value.parse::<usize>() else {
+                    eprintln!("Invalid ply {value} for gamebook");
+                    return None;
+                };
+                options.ply = value;
+            }
+            "min" => {
+                options.min_count = match value.parse::<usize>() {
+                    Ok(value) if value > 0 => value,
+                    _ => {
+                        eprintln!("Invalid min {value} for gamebook");
+                        return None;
+                    }
+                };
+            }
+            "balanced" => {
+                let Ok(value) = value.parse::<i32>() else {
+                    eprintln!("Invalid balanced {value} for gamebook");
+                    return None;
+                };
+                options.balanced = Some(value.abs());
+            }
+            _ => {
+                eprintln!("Unrecognised gamebook option {arg}");
+                return None;
+            }
+        }
+    }
+
+    if options.dir == GameBookOptions::default().dir {
+        eprintln!("Gamebook requires dir=");
+        return None;
+    }
+
+    Some(options)
+}
+
 pub fn parse() -> Option<CliOptions> {
     let args: Vec<String> = std::env::args().skip(1).collect();
 
@@ -277,10 +743,11 @@ pub fn parse() -> Option<CliOptions> {
                     eprintln!("No value for -variant");
                     return None;
                 };
-                if value != "standard" {
+                let Some(variant) = shogi::Variant::parse(value) else {
                     eprintln!("Unrecognised value of -variant");
                     return None;
-                }
+                };
+                options.variant = variant;
             }
 
             "-event" => {
@@ -326,11 +793,6 @@ pub fn parse() -> Option<CliOptions> {
             }
 
             "-openings" => {
-                if options.book.is_some() {
-                    eprintln!("Duplicate -openings flag");
-                    return None;
-                }
-
                 let mut book = BookOptions::default();
                 while let Some(option) = it.peek()
                     && !option.starts_with("-")
@@ -361,8 +823,63 @@ pub fn parse() -> Option<CliOptions> {
                                 return None;
                             }
                         }
+                        "handicap" => {
+                            let Some(handicap) = shogi::Handicap::parse(value) else {
+                                eprintln!("Invalid value {value} for openings handicap option");
+                                return None;
+                            };
+                            book.handicap = Some(handicap);
+                        }
+                        "augment" => match value {
+                            "mirror" => book.mirror = true,
+                            "flip" => book.flip = true,
+                            "both" => (book.mirror, book.flip) = (true, true),
+                            _ => {
+                                eprintln!("Invalid value {value} for openings augment option");
+                                return None;
+                            }
+                        },
+                        "plies" => {
+                            let Ok(value) = value.parse::<usize>() else {
+                                eprintln!("Invalid value {value} for openings plies option");
+                                return None;
+                            };
+                            book.plies = Some(value);
+                        }
+                        "eval" => {
+                            let Ok(value) = value.parse::<i32>() else {
+                                eprintln!("Invalid value {value} for openings eval option");
+                                return None;
+                            };
+                            book.eval_window = Some(value.abs());
+                        }
+                        "count" => match value.parse::<usize>() {
+                            Ok(value) if value > 0 => book.count = value,
+                            _ => {
+                                eprintln!("Invalid value {value} for openings count option");
+                                return None;
+                            }
+                        },
+                        "weight" => match value.parse::<u32>() {
+                            Ok(value) if value > 0 => book.weight = value,
+                            _ => {
+                                eprintln!("Invalid value {value} for openings weight option");
+                                return None;
+                            }
+                        },
+                        "lazy" => match value {
+                            "true" => book.lazy = true,
+                            "false" => book.lazy = false,
+                            _ => {
+                                eprintln!("Invalid value {value} for openings lazy option");
+                                return None;
+                            }
+                        },
                         "format" => match value {
-                            "epd" => {}
+                            "epd" => book.format = BookFormat::Epd,
+                            "csa" => book.format = BookFormat::Csa,
+                            "usi" => book.format = BookFormat::Usi,
+                            "yanedb" => book.format = BookFormat::YaneDb,
                             _ => {
                                 eprintln!("Invalid value {value} for openings format option");
                                 return None;
@@ -374,7 +891,41 @@ pub fn parse() -> Option<CliOptions> {
                         }
                     }
                 }
-                options.book = Some(book);
+                if book.handicap.is_some() && book.file != BookOptions::default().file {
+                    eprintln!("Openings handicap option cannot be combined with an openings file");
+                    return None;
+                }
+                if book.plies.is_some()
+                    && !matches!(
+                        book.format,
+                        BookFormat::Csa | BookFormat::Usi | BookFormat::YaneDb
+                    )
+                {
+                    eprintln!("Openings plies option requires format=csa, usi or yanedb");
+                    return None;
+                }
+                if (book.eval_window.is_some() || book.count != BookOptions::default().count)
+                    && book.format != BookFormat::YaneDb
+                {
+                    eprintln!("Openings eval and count options require format=yanedb");
+                    return None;
+                }
+                if book.lazy
+                    && (book.format != BookFormat::Epd
+                        || book.handicap.is_some()
+                        || book.mirror
+                        || book.flip)
+                {
+                    eprintln!(
+                        "Openings lazy option requires format=epd and cannot be combined with handicap or augment"
+                    );
+                    return None;
+                }
+                if book.handicap.is_some() && (book.mirror || book.flip) {
+                    eprintln!("Openings handicap option cannot be combined with augment");
+                    return None;
+                }
+                options.books.push(book);
             }
 
             "-concurrency" => {
@@ -462,6 +1013,15 @@ pub fn parse() -> Option<CliOptions> {
                         "file" => {
                             pgn_out.file = String::from(value);
                         }
+                        "notation" => match value {
+                            "usi" => pgn_out.notation = MoveNotation::Usi,
+                            "western" => pgn_out.notation = MoveNotation::Western,
+                            "japanese" => pgn_out.notation = MoveNotation::Japanese,
+                            _ => {
+                                eprintln!("Invalid value {value} for -pgnout notation option");
+                                return None;
+                            }
+                        },
                         "nodes" => {
                             pgn_out.track_nodes = value_as_bool()?;
                         }
@@ -494,6 +1054,141 @@ pub fn parse() -> Option<CliOptions> {
                 options.pgn = Some(pgn_out);
             }
 
+            "-kifout" => {
+                let mut kif_out = KifOutOptions::default();
+                while let Some(option) = it.peek()
+                    && !option.starts_with("-")
+                    && let Some((name, value)) = option.split_once('=')
+                {
+                    it.next(); // consume token
+
+                    match name {
+                        "file" => {
+                            kif_out.file = String::from(value);
+                        }
+                        "format" => match value {
+                            "kif" => kif_out.format = KifFormat::Kif,
+                            "ki2" => kif_out.format = KifFormat::Ki2,
+                            _ => {
+                                eprintln!("Invalid value {value} for -kifout format option");
+                                return None;
+                            }
+                        },
+                        _ => {
+                            eprintln!("Invalid key {name} for -kifout");
+                            return None;
+                        }
+                    }
+                }
+                if kif_out.file.is_empty() {
+                    eprintln!("output file required for -kifout option");
+                    return None;
+                }
+                options.kif = Some(kif_out);
+            }
+
+            "-csaout" => {
+                let mut csa_out = CsaOutOptions::default();
+                while let Some(option) = it.peek()
+                    && !option.starts_with("-")
+                    && let Some((name, value)) = option.split_once('=')
+                {
+                    it.next(); // consume token
+
+                    match name {
+                        "file" => {
+                            csa_out.file = String::from(value);
+                        }
+                        _ => {
+                            eprintln!("Invalid key {name} for -csaout");
+                            return None;
+                        }
+                    }
+                }
+                if csa_out.file.is_empty() {
+                    eprintln!("output file required for -csaout option");
+                    return None;
+                }
+                options.csa = Some(csa_out);
+            }
+
+            "-impasse" => {
+                while let Some(option) = it.peek()
+                    && !option.starts_with("-")
+                    && let Some((name, value)) = option.split_once('=')
+                {
+                    it.next(); // consume token
+
+                    match name {
+                        "rule" => {
+                            options.rules.impasse = match value {
+                                "27" => shogi::ImpasseRule::Points27,
+                                "24" => shogi::ImpasseRule::Points24,
+                                "try" => shogi::ImpasseRule::Try,
+                                "none" => shogi::ImpasseRule::None,
+                                _ => {
+                                    eprintln!("Invalid value {value} for -impasse rule option");
+                                    return None;
+                                }
+                            };
+                        }
+                        _ => {
+                            eprintln!("Invalid key {name} for -impasse");
+                            return None;
+                        }
+                    }
+                }
+            }
+
+            "-sennichite" => {
+                while let Some(option) = it.peek()
+                    && !option.starts_with("-")
+                    && let Some((name, value)) = option.split_once('=')
+                {
+                    it.next(); // consume token
+
+                    match name {
+                        "count" => {
+                            options.rules.repetitions = match value.parse::<usize>() {
+                                Ok(value) if value >= 2 => value,
+                                _ => {
+                                    eprintln!("Invalid count {value} for -sennichite");
+                                    return None;
+                                }
+                            };
+                        }
+                        "perpetual" => {
+                            options.rules.perpetual = match value {
+                                "loss" => shogi::PerpetualRule::Loss,
+                                "draw" => shogi::PerpetualRule::Draw,
+                                _ => {
+                                    eprintln!(
+                                        "Invalid value {value} for -sennichite perpetual option"
+                                    );
+                                    return None;
+                                }
+                            };
+                        }
+                        "repetition" => {
+                            options.rules.repetition = match value {
+                                "draw" => Some(shogi::RepetitionRule::Draw),
+                                "loss" => Some(shogi::RepetitionRule::Loss),
+                                _ => {
+                                    eprintln!(
+                                        "Invalid value {value} for -sennichite repetition option"
+                                    );
+                                    return None;
+                                }
+                            };
+                        }
+                        _ => {
+                            eprintln!("Invalid key {name} for -sennichite");
+                            return None;
+                        }
+                    }
+                }
+            }
+
             "-maxmoves" => {
                 let Some(value) = it.next() else { break };
                 options.adjudication.max_moves = match value.to_lowercase().as_str() {
@@ -603,6 +1298,48 @@ pub fn parse() -> Option<CliOptions> {
                 options.adjudication.resign = Some(resign);
             }
 
+            "-adjudicate" => {
+                let mut mate = MateAdjudicationOptions::default();
+                let mut has_mate = false;
+                while let Some(option) = it.peek()
+                    && !option.starts_with("-")
+                    && let Some((name, value)) = option.split_once('=')
+                {
+                    it.next(); // consume token
+
+                    match name {
+                        "mate" => {
+                            mate.plies = match value.parse::<usize>() {
+                                Ok(value) if value > 0 => value,
+                                _ => {
+                                    eprintln!("Invalid mate {value} for -adjudicate");
+                                    return None;
+                                }
+                            };
+                            has_mate = true;
+                        }
+                        "nodes" => {
+                            mate.nodes = match value.parse::<u64>() {
+                                Ok(value) if value > 0 => value,
+                                _ => {
+                                    eprintln!("Invalid nodes {value} for -adjudicate");
+                                    return None;
+                                }
+                            };
+                        }
+                        _ => {
+                            eprintln!("Invalid key {name} for -adjudicate");
+                            return None;
+                        }
+                    }
+                }
+                if !has_mate {
+                    eprintln!("mate=N required for -adjudicate option");
+                    return None;
+                }
+                options.adjudication.mate = Some(mate);
+            }
+
             "-ratinginterval" => {
                 let Some(option) = it.next() else { break };
                 if let Ok(option) = option.parse::<u64>() {
@@ -667,6 +1404,10 @@ pub fn parse() -> Option<CliOptions> {
                 options.sprt = Some(sprt);
             }
 
+            "-listoptions" => {
+                options.list_options = true;
+            }
+
             "-testEnv" => {
                 options.report_interval = None;
             }
@@ -690,10 +1431,46 @@ pub fn parse() -> Option<CliOptions> {
         }
     }
 
+    if options.books.len() > 1 && options.books.iter().any(|b| b.handicap.is_some()) {
+        eprintln!("Openings handicap option cannot be combined with other -openings");
+        return None;
+    }
+
     if options.sprt.is_some() && options.engines.len() != 2 {
         eprintln!("SPRT can only be done on two engines");
         return None;
     }
 
+    if options.variant != shogi::Variant::Standard {
+        if options.kif.is_some() || options.csa.is_some() {
+            eprintln!("KIF and CSA output are only available for standard shogi");
+            return None;
+        }
+        if options.books.iter().any(|b| b.handicap.is_some()) {
+            eprintln!("Handicap openings are only available for standard shogi");
+            return None;
+        }
+    }
+
+    for engine in &mut options.engines {
+        if engine.ponder && engine.builder.get_usi_option_value("USI_Ponder").is_none() {
+            engine
+                .builder
+                .usi_options
+                .push((String::from("USI_Ponder"), String::from("true")));
+        }
+    }
+
+    if let Some(usi_variant) = options.variant.usi_variant() {
+        for engine in &mut options.engines {
+            if engine.builder.get_usi_option_value("USI_Variant").is_none() {
+                engine
+                    .builder
+                    .usi_options
+                    .push((String::from("USI_Variant"), String::from(usi_variant)));
+            }
+        }
+    }
+
     Some(options)
 }
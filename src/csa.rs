@@ -0,0 +1,60 @@
+use crate::{cli, shogi, tournament};
+use std::fs::File;
+use std::io::{Error, Write};
+
+#[derive(Debug)]
+pub struct CsaWriter {
+    file: File,
+    engine_names: Vec<String>,
+    meta: cli::MetaDataOptions,
+    games_written: usize,
+}
+
+impl CsaWriter {
+    pub fn new(
+        options: &cli::CsaOutOptions,
+        meta: &cli::MetaDataOptions,
+        engine_names: Vec<String>,
+    ) -> Result<CsaWriter, Error> {
+        Ok(CsaWriter {
+            file: File::create_new(&options.file)?,
+            engine_names,
+            meta: meta.clone(),
+            games_written: 0,
+        })
+    }
+
+    pub fn write(&mut self, match_result: &tournament::MatchResult) -> Result<(), Error> {
+        let f = &mut self.file;
+        let ticket = &match_result.ticket;
+
+        let mut record = shogi::CsaRecord::new(ticket.opening);
+        record.names = ticket
+            .engines
+            .map(|engine| Some(self.engine_names[engine].clone()));
+        record.event = Some(self.meta.event_name.clone());
+        record.site = Some(self.meta.site_name.clone());
+        record.start_time = Some(
+            match_result
+                .game_start
+                .with_timezone(&chrono::Local)
+                .format("%Y/%m/%d %H:%M:%S")
+                .to_string(),
+        );
+        record.moves = match_result
+            .moves
+            .iter()
+            .map(|m| (m.m, Some(m.measured_time)))
+            .collect();
+        record.outcome = match_result.outcome;
+
+        // Multiple games in one file are separated by a line containing a single "/"
+        if self.games_written > 0 {
+            writeln!(f, "/")?;
+        }
+        write!(f, "{record}")?;
+        self.games_written += 1;
+
+        Ok(())
+    }
+}
@@ -35,6 +35,8 @@ pub struct MoveRecord {
     pub stm: Option<shogi::Color>,
     pub m: shogi::Move,
     pub mstr: String,
+    // The reply the engine expects, from `bestmove ... ponder MOVE`
+    pub ponder: Option<shogi::Move>,
     pub score: Score,
     pub depth: u32,
     pub seldepth: u32,
@@ -46,6 +48,158 @@ pub struct MoveRecord {
     pub time_left: Option<Duration>,
 }
 
+/// The type of a USI option, with the default and limits the engine declared for it.
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub enum UsiOptionKind {
+    Check {
+        default: Option<bool>,
+    },
+    Spin {
+        default: Option<i64>,
+        min: Option<i64>,
+        max: Option<i64>,
+    },
+    Combo {
+        default: Option<String>,
+        vars: Vec<String>,
+    },
+    Button,
+    String {
+        default: Option<String>,
+    },
+    Filename {
+        default: Option<String>,
+    },
+}
+
+/// An option declared by an engine with `option name ... type ...` before `usiok`.
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct UsiOption {
+    pub name: String,
+    pub kind: UsiOptionKind,
+}
+
+impl UsiOption {
+    /// Parses an `option` line. Returns None for lines that are not a valid declaration.
+    pub fn parse(line: &str) -> Option<UsiOption> {
+        let mut it = line.split_whitespace();
+        if it.next()? != "option" {
+            return None;
+        }
+        let mut fields: Vec<(&str, Vec<&str>)> = vec![];
+        for tok in it {
+            match tok {
+                "name" | "type" | "default" | "min" | "max" | "var" => fields.push((tok, vec![])),
+                _ => fields.last_mut()?.1.push(tok),
+            }
+        }
+        let values = |key| {
+            fields
+                .iter()
+                .filter(move |(k, _)| *k == key)
+                .map(|(_, v)| v.join(" "))
+        };
+        let value = |key| values(key).next();
+        let int = |key| value(key).and_then(|v| v.parse::<i64>().ok());
+
+        let name = value("name").filter(|name| !name.is_empty())?;
+        let default = value("default");
+        let kind = match value("type")?.as_str() {
+            "check" => UsiOptionKind::Check {
+                default: default.and_then(|d| d.parse().ok()),
+            },
+            "spin" => UsiOptionKind::Spin {
+                default: int("default"),
+                min: int("min"),
+                max: int("max"),
+            },
+            "combo" => UsiOptionKind::Combo {
+                default,
+                vars: values("var").collect(),
+            },
+            "button" => UsiOptionKind::Button,
+            "string" => UsiOptionKind::String { default },
+            "filename" => UsiOptionKind::Filename { default },
+            _ => return None,
+        };
+        Some(UsiOption { name, kind })
+    }
+
+    /// Checks that `value` can be set for this option, returning the reason if it cannot.
+    pub fn check_value(&self, value: &str) -> std::result::Result<(), String> {
+        match &self.kind {
+            UsiOptionKind::Check { .. } => match value {
+                "true" | "false" => Ok(()),
+                _ => Err(format!("{value} is not true or false")),
+            },
+            UsiOptionKind::Spin { min, max, .. } => match value.parse::<i64>() {
+                Ok(v)
+                    if let Some(min) = *min
+                        && v < min =>
+                {
+                    Err(format!("{v} is less than the minimum {min}"))
+                }
+                Ok(v)
+                    if let Some(max) = *max
+                        && v > max =>
+                {
+                    Err(format!("{v} is more than the maximum {max}"))
+                }
+                Ok(_) => Ok(()),
+                Err(_) => Err(format!("{value} is not an integer")),
+            },
+            UsiOptionKind::Combo { vars, .. } => {
+                if vars.iter().any(|var| var == value) {
+                    Ok(())
+                } else {
+                    Err(format!("{value} is not one of {}", vars.join(", ")))
+                }
+            }
+            UsiOptionKind::Button
+            | UsiOptionKind::String { .. }
+            | UsiOptionKind::Filename { .. } => Ok(()),
+        }
+    }
+}
+
+impl std::fmt::Display for UsiOption {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        let (kind, default) = match &self.kind {
+            UsiOptionKind::Check { default } => ("check", default.map(|d| d.to_string())),
+            UsiOptionKind::Spin { default, .. } => ("spin", default.map(|d| d.to_string())),
+            UsiOptionKind::Combo { default, .. } => ("combo", default.clone()),
+            UsiOptionKind::Button => ("button", None),
+            UsiOptionKind::String { default } => ("string", default.clone()),
+            UsiOptionKind::Filename { default } => ("filename", default.clone()),
+        };
+        write!(f, "{} ({kind}", self.name)?;
+        if let Some(default) = default {
+            write!(f, ", default {default}")?;
+        }
+        match &self.kind {
+            UsiOptionKind::Spin { min, max, .. } => {
+                if let Some(min) = min {
+                    write!(f, ", min {min}")?;
+                }
+                if let Some(max) = max {
+                    write!(f, ", max {max}")?;
+                }
+            }
+            UsiOptionKind::Combo { vars, .. } => write!(f, ", values {}", vars.join("|"))?,
+            _ => {}
+        }
+        write!(f, ")")
+    }
+}
+
+/// What an engine reported about itself in reply to `usi`.
+#[derive(Debug, Clone, PartialEq, Eq, Default)]
+pub struct EngineInfo {
+    pub name: Option<String>,
+    pub author: Option<String>,
+    pub options: Vec<UsiOption>,
+}
+
 #[derive(Clone, PartialEq, Eq, Debug, Default)]
 pub struct EngineBuilder {
     pub dir: String,
@@ -77,11 +231,12 @@ impl EngineBuilder {
             stdin,
             name: self.name.clone().unwrap_or(self.cmd.to_string()),
             builder: self.clone(),
+            info: EngineInfo::default(),
         };
 
         engine.write_line("usi")?;
 
-        let mut usi_name: Option<String> = None;
+        let mut info = EngineInfo::default();
         match engine.read_with_timeout(Some(5 * Duration::SECOND), |line| {
             let mut it = line.split_whitespace();
             match it.next() {
@@ -90,16 +245,27 @@ impl EngineBuilder {
                     match it.next() {
                         Some("name") => {
                             if let Some(name) = it.remainder() {
-                                usi_name = Some(name.trim().to_string());
+                                info.name = Some(name.trim().to_string());
+                            }
+                        }
+                        Some("author") => {
+                            if let Some(author) = it.remainder() {
+                                info.author = Some(author.trim().to_string());
                             }
                         }
-                        Some("author") => {}
                         s => {
                             dbg!(s);
                         }
                     }
                     ReadState::Continue
                 }
+                Some("option") => {
+                    match UsiOption::parse(&line) {
+                        Some(option) => info.options.push(option),
+                        None => error!("Ignoring invalid option declaration: {}", line.trim()),
+                    }
+                    ReadState::Continue
+                }
                 _ => ReadState::Continue,
             }
         }) {
@@ -122,14 +288,38 @@ impl EngineBuilder {
             }
         }
 
-        if let Some(usi_name) = usi_name
+        if let Some(usi_name) = &info.name
             && self.name.is_none()
         {
-            engine.name = usi_name;
+            engine.name = usi_name.clone();
         }
+        engine.info = info;
 
         for (k, v) in &self.usi_options {
-            engine.write_line(&format!("setoption name {k} value {v}"))?;
+            // Options reserved by the protocol, such as USI_Variant, need not be declared
+            let option = engine.info.options.iter().find(|option| option.name == *k);
+            if let Some(option) = option {
+                if let Err(err) = option.check_value(v) {
+                    return Err(std::io::Error::new(
+                        std::io::ErrorKind::InvalidInput,
+                        format!(
+                            "Invalid value for option {k} of engine {}: {err}",
+                            engine.name
+                        ),
+                    ));
+                }
+            } else if !k.starts_with("USI_") {
+                return Err(std::io::Error::new(
+                    std::io::ErrorKind::InvalidInput,
+                    format!("Engine {} has no option named {k}", engine.name),
+                ));
+            }
+
+            if option.is_some_and(|option| option.kind == UsiOptionKind::Button) {
+                engine.write_line(&format!("setoption name {k}"))?;
+            } else {
+                engine.write_line(&format!("setoption name {k} value {v}"))?;
+            }
         }
 
         info!("Engine {} started", engine.name);
@@ -152,6 +342,7 @@ pub struct Engine {
     stdin: ChildStdin,
     name: String,
     builder: EngineBuilder,
+    info: EngineInfo,
 }
 
 impl Drop for Engine {
@@ -182,6 +373,10 @@ impl Engine {
         &self.name
     }
 
+    pub fn info(&self) -> &EngineInfo {
+        &self.info
+    }
+
     pub fn restart(&mut self) -> Result<()> {
         *self = self.builder.init()?;
         Ok(())
@@ -224,6 +419,23 @@ impl Engine {
         Ok(())
     }
 
+    pub fn ponderhit(&mut self) -> Result<()> {
+        self.write_line("ponderhit")?;
+        self.flush()?;
+        Ok(())
+    }
+
+    /// Ends a `go ponder` search with `stop` and discards the move it returns.
+    pub fn stop_pondering(&mut self, timeout: Option<Duration>) -> EngineResult<()> {
+        if let Err(err) = self.write_line("stop").and_then(|_| self.flush()) {
+            return EngineResult::Err(err);
+        }
+        self.read_with_timeout(timeout, |line| match line.split_ascii_whitespace().next() {
+            Some("bestmove") => ReadState::Stop,
+            _ => ReadState::Continue,
+        })
+    }
+
     pub fn position(&mut self, game: &shogi::Game) -> Result<()> {
         let position = format!("position {}", game.usi_string());
         self.write_line(&position)?;
@@ -320,6 +532,9 @@ impl Engine {
                     if let Some(m) = shogi::Move::parse(mstr) {
                         mr.m = m;
                     }
+                    if it.next() == Some("ponder") {
+                        mr.ponder = it.next().and_then(shogi::Move::parse);
+                    }
                     ReadState::Stop
                 }
                 _ => ReadState::Continue,
@@ -332,6 +547,42 @@ impl Engine {
         }
     }
 
+    /// Runs `go perft` and collects the engine's divide output, given as `MOVE: COUNT` lines
+    /// and terminated by a `Nodes searched` line.
+    pub fn divide(
+        &mut self,
+        position: &shogi::Position,
+        depth: usize,
+    ) -> EngineResult<Vec<(String, u64)>> {
+        if let Err(err) = self.write_line(&format!("position sfen {position}")) {
+            return EngineResult::Err(err);
+        }
+        if let Err(err) = self.write_line(&format!("go perft {depth}")) {
+            return EngineResult::Err(err);
+        }
+        if let Err(err) = self.flush() {
+            return EngineResult::Err(err);
+        }
+
+        let mut counts = Vec::new();
+        match self.read_with_timeout(None, |line| {
+            if line.trim_start().starts_with("Nodes searched") {
+                return ReadState::Stop;
+            }
+            if let Some((mstr, count)) = line.trim().split_once(':')
+                && let Ok(count) = count.trim().parse::<u64>()
+            {
+                counts.push((mstr.trim().to_string(), count));
+            }
+            ReadState::Continue
+        }) {
+            EngineResult::Ok(()) => EngineResult::Ok(counts),
+            EngineResult::Err(err) => EngineResult::Err(err),
+            EngineResult::Timeout => EngineResult::Timeout,
+            EngineResult::Disconnected => EngineResult::Disconnected,
+        }
+    }
+
     pub fn flush(&mut self) -> Result<()> {
         self.stdin.flush()
     }
@@ -515,3 +766,135 @@ impl Engine {
         Ok(ReadState::Continue)
     }
 }
+
+#[cfg(test)]
+pub(crate) mod tests {
+    use super::*;
+
+    /// Writes a shell script that answers like a USI engine and logs every command it receives to
+    /// `CMD.log`. `respond` holds `case` arms matched against "COMMAND PLY", where COMMAND is `go`,
+    /// `ponder` (for `go ponder`), `ponderhit` or `stop`, and PLY is the number of moves in the last
+    /// `position` command.
+    #[cfg(unix)]
+    pub(crate) fn scripted_engine(name: &str, respond: &str) -> EngineBuilder {
+        use std::os::unix::fs::PermissionsExt;
+
+        let cmd = std::env::temp_dir().join(format!("shogitest-{}-{name}.sh", std::process::id()));
+        let script = format!(
+            r#"#!/bin/sh
+set -f
+n=0
+while read -r line; do
+    echo "$line" >> "$0.log"
+    set -- $line
+    cmd=$1
+    case "$1" in
+        usi) echo "id name {name}"; echo "usiok"; continue ;;
+        isready) echo "readyok"; continue ;;
+        quit) exit 0 ;;
+        position) if [ $# -gt 6 ]; then n=$(($# - 7)); else n=0; fi; continue ;;
+        go) if [ "$2" = ponder ]; then cmd=ponder; fi ;;
+    esac
+    case "$cmd $n" in
+{respond}
+    esac
+done
+"#
+        );
+        std::fs::write(&cmd, script).unwrap();
+        std::fs::set_permissions(&cmd, std::fs::Permissions::from_mode(0o755)).unwrap();
+        let _ = std::fs::remove_file(format!("{}.log", cmd.display()));
+        EngineBuilder {
+            cmd: cmd.to_string_lossy().to_string(),
+            ..EngineBuilder::default()
+        }
+    }
+
+    #[test]
+    fn parse_usi_options() {
+        let hash = UsiOption::parse("option name USI_Hash type spin default 256 min 1 max 4096");
+        let hash = hash.unwrap();
+        assert_eq!(
+            UsiOptionKind::Spin {
+                default: Some(256),
+                min: Some(1),
+                max: Some(4096)
+            },
+            hash.kind
+        );
+        assert_eq!(Ok(()), hash.check_value("4096"));
+        assert!(hash.check_value("0").is_err());
+        assert!(hash.check_value("big").is_err());
+
+        let style = UsiOption::parse(
+            "option name Style type combo default Normal var Solid var Normal var Risky",
+        )
+        .unwrap();
+        assert_eq!(
+            "Style (combo, default Normal, values Solid|Normal|Risky)",
+            style.to_string()
+        );
+        assert_eq!(Ok(()), style.check_value("Risky"));
+        assert!(style.check_value("risky").is_err());
+
+        let ponder = UsiOption::parse("option name USI_Ponder type check default false").unwrap();
+        assert_eq!(
+            UsiOptionKind::Check {
+                default: Some(false)
+            },
+            ponder.kind
+        );
+        assert!(ponder.check_value("on").is_err());
+
+        let book = UsiOption::parse("option name BookFile type filename default book/a b.db");
+        assert_eq!(
+            "BookFile (filename, default book/a b.db)",
+            book.unwrap().to_string()
+        );
+        assert_eq!(
+            UsiOptionKind::Button,
+            UsiOption::parse("option name Clear Hash type button")
+                .unwrap()
+                .kind
+        );
+
+        assert_eq!(None, UsiOption::parse("option name Hash"));
+        assert_eq!(None, UsiOption::parse("option name Hash type slider"));
+        assert_eq!(None, UsiOption::parse("option type spin default 1"));
+    }
+
+    #[cfg(unix)]
+    #[test]
+    fn bestmove_with_ponder() {
+        let builder = scripted_engine(
+            "bestmove",
+            r#"        "go 0") echo "info depth 3 score cp 12"; echo "bestmove 7g7f ponder 3c3d" ;;
+        "go 1") echo "bestmove resign" ;;"#,
+        );
+        let mut engine = builder.init().unwrap();
+        let mut game = shogi::Game::new(shogi::Position::default());
+        let search = |engine: &mut Engine, game: &shogi::Game| {
+            engine.position(game).unwrap();
+            engine.write_line("go byoyomi 1000").unwrap();
+            engine.flush().unwrap();
+            match engine.wait_for_bestmove(game.stm(), Some(5 * Duration::SECOND)) {
+                EngineResult::Ok(mr) => mr,
+                result => panic!("{result:?}"),
+            }
+        };
+
+        let mr = search(&mut engine, &game);
+        assert_eq!(shogi::Move::parse("7g7f"), Some(mr.m));
+        assert_eq!(shogi::Move::parse("3c3d"), mr.ponder);
+        assert!(matches!(mr.score, Score::Cp(12)));
+
+        game.do_move(mr.m);
+        let mr = search(&mut engine, &game);
+        assert_eq!(shogi::Move::Resign, mr.m);
+        assert_eq!(None, mr.ponder);
+
+        drop(engine);
+        std::fs::remove_file(&builder.cmd).unwrap();
+        std::fs::remove_file(format!("{}.log", builder.cmd)).unwrap();
+    }
+}
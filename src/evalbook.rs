@@ -0,0 +1,149 @@
+use crate::book::OpeningBook;
+use crate::cli;
+use crate::engine::{Engine, EngineResult, Score};
+use crate::shogi::{Game, Position};
+use crate::util;
+use std::fs::File;
+use std::io::{self, Write};
+use std::time::Duration;
+
+// Index of the output a position with eval `cp` is written to, or None if it is filtered out.
+// Without buckets everything inside the window goes to the single output.
+fn destination(cp: i32, window: Option<i32>, buckets: &[i32]) -> Option<usize> {
+    if window.is_some_and(|window| cp.abs() > window) {
+        return None;
+    }
+    if buckets.is_empty() {
+        return Some(0);
+    }
+    buckets.iter().position(|&limit| cp.abs() <= limit)
+}
+
+// Searches `position` with a fixed budget and returns the final score, from the point of view of
+// the side to move.
+fn evaluate(
+    engine: &mut Engine,
+    position: Position,
+    options: &cli::EvalBookOptions,
+) -> EngineResult<Score> {
+    let go = match options.movetime {
+        Some(movetime) => format!("go btime 0 wtime 0 byoyomi {}", movetime.as_millis()),
+        None => format!("go nodes {}", options.nodes),
+    };
+    let timeout = match options.movetime {
+        Some(movetime) => movetime + 5 * Duration::SECOND,
+        None => options.timeout,
+    };
+
+    let result = engine
+        .usinewgame()
+        .and_then(|_| engine.position(&Game::new(position)))
+        .and_then(|_| engine.write_line(&go))
+        .and_then(|_| engine.flush());
+    if let Err(err) = result {
+        return EngineResult::Err(err);
+    }
+    match engine.wait_for_bestmove(position.stm(), Some(timeout)) {
+        EngineResult::Ok(mr) => EngineResult::Ok(mr.score),
+        EngineResult::Err(err) => EngineResult::Err(err),
+        EngineResult::Timeout => EngineResult::Timeout,
+        EngineResult::Disconnected => EngineResult::Disconnected,
+    }
+}
+
+pub fn run(options: &cli::EvalBookOptions) -> io::Result<()> {
+    let Some(positions) = OpeningBook::read_epd(&options.file) else {
+        return Ok(());
+    };
+
+    let mut builder = options.engine.clone();
+    if let Some(position) = positions.first()
+        && let Some(usi_variant) = position.variant().usi_variant()
+        && builder.get_usi_option_value("USI_Variant").is_none()
+    {
+        builder
+            .usi_options
+            .push((String::from("USI_Variant"), String::from(usi_variant)));
+    }
+    let mut engine = builder.init()?;
+    engine.isready()?;
+
+    let mut outputs: Vec<Box<dyn Write>> = match &options.out {
+        Some(file) if !options.buckets.is_empty() => (1..=options.buckets.len())
+            .map(|i| {
+                let file = File::create(util::numbered_file_name(file, i as u64))?;
+                Ok(Box::new(io::BufWriter::new(file)) as Box<dyn Write>)
+            })
+            .collect::<io::Result<_>>()?,
+        Some(file) => vec![Box::new(io::BufWriter::new(File::create(file)?))],
+        None => vec![Box::new(io::stdout().lock())],
+    };
+    let mut kept = vec![0; outputs.len()];
+    let mut mates = 0;
+    let mut unscored = 0;
+
+    for (i, &position) in positions.iter().enumerate() {
+        let cp = match evaluate(&mut engine, position, options) {
+            EngineResult::Ok(Score::Cp(cp)) => cp,
+            EngineResult::Ok(Score::Mate(_)) => {
+                mates += 1;
+                continue;
+            }
+            EngineResult::Ok(Score::None) => {
+                unscored += 1;
+                continue;
+            }
+            EngineResult::Err(err) => return Err(err),
+            EngineResult::Timeout | EngineResult::Disconnected => {
+                eprintln!("Engine {} did not finish position {}", engine.name(), i + 1);
+                break;
+            }
+        };
+        let Some(index) = destination(cp, options.window, &options.buckets) else {
+            continue;
+        };
+        if options.annotate {
+            writeln!(outputs[index], "{position}; eval {cp}")?;
+        } else {
+            writeln!(outputs[index], "{position}")?;
+        }
+        kept[index] += 1;
+    }
+    for output in &mut outputs {
+        output.flush()?;
+    }
+
+    eprintln!(
+        "Kept {} of {} positions ({mates} dropped with a mate score, {unscored} without a score)",
+        kept.iter().sum::<usize>(),
+        positions.len()
+    );
+    if let Some(file) = &options.out
+        && !options.buckets.is_empty()
+    {
+        for (i, count) in kept.iter().enumerate() {
+            let file = util::numbered_file_name(file, i as u64 + 1);
+            eprintln!("{file}: {count} positions");
+        }
+    }
+
+    Ok(())
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn eval_destination() {
+        assert_eq!(Some(0), destination(-500, None, &[]));
+        assert_eq!(Some(0), destination(-50, Some(50), &[]));
+        assert_eq!(None, destination(51, Some(50), &[]));
+
+        let buckets = [30, 100];
+        assert_eq!(Some(0), destination(-30, None, &buckets));
+        assert_eq!(Some(1), destination(31, None, &buckets));
+        assert_eq!(None, destination(101, None, &buckets));
+        assert_eq!(None, destination(80, Some(60), &buckets));
+    }
+}
@@ -0,0 +1,226 @@
+use crate::engine::Score;
+use crate::{cli, kif, pgn, shogi};
+use std::collections::HashMap;
+use std::fs::{self, File};
+use std::io::{self, Write};
+use std::path::PathBuf;
+
+// A game from a PGN, CSA or KIF file. Moves that cannot be parsed are None.
+struct Record {
+    start: shogi::Position,
+    moves: Vec<(Option<shogi::Move>, Score)>,
+}
+
+impl Record {
+    // The position after the first `ply` moves, or None if the game ended or went wrong earlier.
+    fn position_at(&self, ply: usize) -> Option<shogi::Position> {
+        if self.moves.len() < ply {
+            return None;
+        }
+        let mut game = shogi::Game::new(self.start);
+        for &(m, _) in &self.moves[..ply] {
+            if game.do_move(m?).is_determined() {
+                return None;
+            }
+        }
+        Some(*game.position())
+    }
+
+    // Whether the engines scored the position after `ply` moves within `window` centipawns of zero
+    // on both of the moves played from it.
+    fn is_balanced(&self, ply: usize, window: i32) -> bool {
+        let scores = self.moves.iter().skip(ply).take(2);
+        scores.len() == 2
+            && scores
+                .map(|(_, score)| score)
+                .all(|score| matches!(*score, Score::Cp(cp) if cp.abs() <= window))
+    }
+}
+
+fn read_records(path: &PathBuf) -> Option<Vec<Record>> {
+    let Ok(text) = fs::read_to_string(path) else {
+        eprintln!("Unable to read {}", path.display());
+        return None;
+    };
+    match path.extension().and_then(|ext| ext.to_str()) {
+        Some("pgn") => Some(pgn::read_games(&text).iter().map(pgn_record).collect()),
+        Some("csa") => match shogi::CsaRecord::parse_all(&text) {
+            Ok(records) => Some(
+                records
+                    .into_iter()
+                    .map(|record| Record {
+                        start: record.start,
+                        moves: record
+                            .moves
+                            .iter()
+                            .map(|&(m, _)| (Some(m), Score::None))
+                            .collect(),
+                    })
+                    .collect(),
+            ),
+            Err(err) => {
+                eprintln!("Invalid CSA record in {}: {err}", path.display());
+                None
+            }
+        },
+        Some("kif" | "ki2") => match kif::read_game(&text) {
+            Ok(game) => Some(vec![Record {
+                start: game.start,
+                moves: game.moves.iter().map(|&m| (Some(m), Score::None)).collect(),
+            }]),
+            Err(err) => {
+                eprintln!("Invalid KIF record in {}: {err}", path.display());
+                None
+            }
+        },
+        _ => None,
+    }
+}
+
+// Replays a PGN game to read its moves in whichever notation they were written. Moves from the
+// first one that cannot be played on are None.
+fn pgn_record(game: &pgn::PgnGame) -> Record {
+    let mut position = Some(game.start);
+    let mut last_to = None;
+    let moves = game
+        .moves
+        .iter()
+        .map(|(mstr, score)| {
+            let m = position.and_then(|p| pgn::parse_move(&p, mstr, last_to));
+            position = position.zip(m).and_then(|(p, m)| p.do_move(m));
+            if let Some(shogi::Move::Normal { to, .. } | shogi::Move::Drop(_, to)) = m {
+                last_to = Some(to);
+            }
+            (m, score.clone())
+        })
+        .collect();
+    Record {
+        start: game.start,
+        moves,
+    }
+}
+
+// Positions at `options.ply` reached at least `options.min_count` times, most frequent first.
+fn collect(records: &[Record], options: &cli::GameBookOptions) -> Vec<(shogi::Position, usize)> {
+    // Position key -> index into `positions`
+    let mut index = HashMap::new();
+    let mut positions: Vec<(shogi::Position, usize)> = vec![];
+    for record in records {
+        if let Some(window) = options.balanced
+            && !record.is_balanced(options.ply, window)
+        {
+            continue;
+        }
+        let Some(position) = record.position_at(options.ply) else {
+            continue;
+        };
+        let i = *index.entry(position.key()).or_insert_with(|| {
+            positions.push((position, 0));
+            positions.len() - 1
+        });
+        positions[i].1 += 1;
+    }
+    positions.retain(|&(_, count)| count >= options.min_count);
+    positions.sort_by_key(|&(_, count)| std::cmp::Reverse(count));
+    positions
+}
+
+pub fn run(options: &cli::GameBookOptions) -> io::Result<()> {
+    let mut paths: Vec<PathBuf> = fs::read_dir(&options.dir)?
+        .map(|entry| entry.map(|entry| entry.path()))
+        .collect::<io::Result<_>>()?;
+    paths.sort();
+
+    let records: Vec<Record> = paths.iter().filter_map(read_records).flatten().collect();
+    let positions = collect(&records, options);
+
+    let mut out: Box<dyn Write> = match &options.out {
+        Some(file) => Box::new(io::BufWriter::new(File::create(file)?)),
+        None => Box::new(io::stdout().lock()),
+    };
+    for (position, _) in &positions {
+        writeln!(out, "{position}")?;
+    }
+    out.flush()?;
+
+    eprintln!(
+        "Read {} games, wrote {} positions",
+        records.len(),
+        positions.len()
+    );
+    Ok(())
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn record(moves: &str, scores: &[i32]) -> Record {
+        Record {
+            start: shogi::Position::default(),
+            moves: moves
+                .split(' ')
+                .enumerate()
+                .map(|(i, mstr)| {
+                    let score = scores.get(i).map_or(Score::None, |&cp| Score::Cp(cp));
+                    (shogi::Move::parse(mstr), score)
+                })
+                .collect(),
+        }
+    }
+
+    #[test]
+    fn pgn_notations() {
+        let text = "[Event \"?\"]\n\nP-7f {+0.10 1}\nP-3d {-0.10 1}\n1/2-1/2\n\n[Event \"?\"]\n\n▲７六歩 {+0.10 1}\n△３四歩 {-0.10 1}\n▲１一飛 {+0.10 1}\n1/2-1/2\n";
+        let after_7g7f_3c3d = "lnsgkgsnl/1r5b1/pppppp1pp/6p2/9/2P6/PP1PPPPPP/1B5R1/LNSGKGSNL b - 3";
+        let records: Vec<Record> = pgn::read_games(text).iter().map(pgn_record).collect();
+        assert_eq!(2, records.len());
+        for record in &records {
+            let position = record.position_at(2).unwrap();
+            assert_eq!(after_7g7f_3c3d, position.to_string());
+        }
+        // A move that cannot be played ends the readable part of the game
+        assert_eq!(None, records[1].moves[2].0);
+        assert!(records[1].is_balanced(1, 10));
+    }
+
+    #[test]
+    fn collect_positions() {
+        let records = [
+            record("7g7f 3c3d 2g2f 8c8d", &[0, 0, 20, -30]),
+            record("7g7f 3c3d 2g2f 4c4d", &[0, 0, 200, -30]),
+            record("2g2f 3c3d 7g7f 8c8d resign", &[0, 0, 10, -10]),
+            record("2g2f 8c8d 7g7f 8d8e", &[0, 0, 10, -10]),
+            record("7g7f resign", &[]),
+            record("7g7f xxxx 2g2f 8c8d", &[]),
+        ];
+        let after_7g7f_3c3d = "lnsgkgsnl/1r5b1/pppppp1pp/6p2/9/2P6/PP1PPPPPP/1B5R1/LNSGKGSNL b - 3";
+
+        let options = cli::GameBookOptions {
+            ply: 2,
+            ..cli::GameBookOptions::default()
+        };
+        let positions = collect(&records, &options);
+        assert_eq!(3, positions.len());
+        assert_eq!(after_7g7f_3c3d, positions[0].0.to_string());
+        assert_eq!(2, positions[0].1);
+
+        let options = cli::GameBookOptions {
+            ply: 2,
+            min_count: 2,
+            balanced: Some(50),
+            ..cli::GameBookOptions::default()
+        };
+        assert!(collect(&records, &options).is_empty());
+
+        // Transpositions count as the same position
+        let options = cli::GameBookOptions {
+            ply: 4,
+            min_count: 2,
+            ..cli::GameBookOptions::default()
+        };
+        let positions = collect(&records, &options);
+        assert_eq!(1, positions.len());
+        assert_eq!(2, positions[0].1);
+    }
+}
@@ -0,0 +1,163 @@
+use crate::book::OpeningBook;
+use crate::cli;
+use crate::shogi::{Color, HAND_PTYPES, Move, PieceType, Position, Square};
+use rand::{Rng, SeedableRng};
+use std::collections::HashSet;
+use std::fs::File;
+use std::io::{self, Write};
+
+// Rough piece values in pawns, for the material balance filter. Promoted pieces count as the
+// piece they promoted from.
+fn piece_value(pt: PieceType) -> i32 {
+    match pt.demote() {
+        PieceType::Pawn => 1,
+        PieceType::Lance => 3,
+        PieceType::Knight => 4,
+        PieceType::Silver => 5,
+        PieceType::Gold => 6,
+        PieceType::Bishop => 8,
+        PieceType::Rook => 10,
+        _ => 0,
+    }
+}
+
+// Material of Sente minus material of Gote, counting both the board and the hands.
+fn material_balance(position: &Position) -> i32 {
+    let sign = |color| if color == Color::Sente { 1 } else { -1 };
+    let size = position.variant().size();
+    let board: i32 = itertools::iproduct!(0..size, 0..size)
+        .filter_map(|(file, rank)| position.piece_at(Square::new(file, rank).unwrap()))
+        .map(|(color, pt)| sign(color) * piece_value(pt))
+        .sum();
+    let hands: i32 = [Color::Sente, Color::Gote]
+        .into_iter()
+        .flat_map(|color| HAND_PTYPES.map(move |pt| (color, pt)))
+        .map(|(color, pt)| sign(color) * piece_value(pt) * position.hand(color).get(pt) as i32)
+        .sum();
+    board + hands
+}
+
+// Plays `options.plies` random legal moves from `start`. Returns None if the walk breaks one of
+// the filters or ends in a position without legal moves.
+fn random_walk<R>(start: Position, options: &cli::GenbookOptions, rng: &mut R) -> Option<Position>
+where
+    R: Rng + ?Sized,
+{
+    let mut position = start;
+    for _ in 0..options.plies {
+        let moves = position.legal_moves();
+        if moves.is_empty() {
+            return None;
+        }
+        let m = moves[rng.random_range(0..moves.len())];
+        if options.no_captures
+            && let Move::Normal { to, .. } = m
+            && position.piece_at(to).is_some()
+        {
+            return None;
+        }
+        position = position.do_move(m).unwrap();
+    }
+
+    if position.legal_moves().is_empty() {
+        return None;
+    }
+    if options.no_check && position.is_in_check() {
+        return None;
+    }
+    if let Some(max_material) = options.max_material
+        && material_balance(&position).abs() > max_material
+    {
+        return None;
+    }
+    Some(position)
+}
+
+// Generates up to `options.count` distinct positions, giving up after a fixed number of
+// rejected walks per position.
+fn generate<R>(starts: &[Position], options: &cli::GenbookOptions, rng: &mut R) -> Vec<Position>
+where
+    R: Rng + ?Sized,
+{
+    const ATTEMPTS_PER_POSITION: usize = 1000;
+
+    let mut seen = HashSet::new();
+    let mut positions = Vec::with_capacity(options.count);
+    for _ in 0..options.count * ATTEMPTS_PER_POSITION {
+        if positions.len() >= options.count {
+            break;
+        }
+        let start = starts[rng.random_range(0..starts.len())];
+        if let Some(position) = random_walk(start, options, rng)
+            && seen.insert(position.key())
+        {
+            positions.push(position);
+        }
+    }
+    positions
+}
+
+pub fn run(options: &cli::GenbookOptions) -> io::Result<()> {
+    let starts = match &options.book {
+        Some(file) => match OpeningBook::read_epd(file) {
+            Some(starts) if !starts.is_empty() => starts,
+            _ => return Ok(()),
+        },
+        None => vec![Position::default()],
+    };
+
+    let mut rng = match options.seed {
+        Some(seed) => rand_chacha::ChaCha8Rng::seed_from_u64(seed),
+        None => rand_chacha::ChaCha8Rng::from_os_rng(),
+    };
+    let positions = generate(&starts, options, &mut rng);
+    if positions.len() < options.count {
+        eprintln!(
+            "Only {} of {} positions could be generated with these filters",
+            positions.len(),
+            options.count
+        );
+    }
+
+    let mut out: Box<dyn Write> = match &options.out {
+        Some(file) => Box::new(io::BufWriter::new(File::create(file)?)),
+        None => Box::new(io::stdout().lock()),
+    };
+    for position in &positions {
+        writeln!(out, "{position}")?;
+    }
+    out.flush()
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn generated_positions_pass_filters() {
+        let options = cli::GenbookOptions {
+            count: 50,
+            plies: 6,
+            no_check: true,
+            no_captures: true,
+            max_material: Some(0),
+            ..cli::GenbookOptions::default()
+        };
+        let starts = [Position::default()];
+        let mut rng = rand_chacha::ChaCha8Rng::seed_from_u64(1);
+        let positions = generate(&starts, &options, &mut rng);
+
+        assert_eq!(50, positions.len());
+        let keys: HashSet<u64> = positions.iter().map(|p| p.key()).collect();
+        assert_eq!(50, keys.len());
+        for position in &positions {
+            assert_eq!(7, position.ply());
+            assert!(!position.is_in_check());
+            assert_eq!(0, material_balance(position));
+            assert!(position.validate().is_empty());
+        }
+
+        let mut rng = rand_chacha::ChaCha8Rng::seed_from_u64(1);
+        assert_eq!(positions, generate(&starts, &options, &mut rng));
+    }
+}
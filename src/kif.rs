@@ -0,0 +1,695 @@
+use crate::{
+    cli,
+    shogi::{Color, GameOutcome, Handicap, Move, PieceType, Position, Square},
+    tournament, util,
+};
+use std::fs::File;
+use std::io::{Error, ErrorKind, Write};
+use std::path::Path;
+use std::time::Duration;
+
+const BOARD_PTYPES: [PieceType; 14] = [
+    PieceType::King,
+    PieceType::Rook,
+    PieceType::Bishop,
+    PieceType::Gold,
+    PieceType::Silver,
+    PieceType::Knight,
+    PieceType::Lance,
+    PieceType::Pawn,
+    PieceType::Dragon,
+    PieceType::Horse,
+    PieceType::NariSilver,
+    PieceType::NariKnight,
+    PieceType::NariLance,
+    PieceType::Tokin,
+];
+
+// Terminating special moves, as written by `special_move`.
+const SPECIAL_MOVES: [&str; 9] = [
+    "中断",
+    "詰み",
+    "入玉勝ち",
+    "千日手",
+    "反則負け",
+    "反則勝ち",
+    "投了",
+    "切れ負け",
+    "持将棋",
+];
+
+const HAND_ORDER: [PieceType; 7] = [
+    PieceType::Rook,
+    PieceType::Bishop,
+    PieceType::Gold,
+    PieceType::Silver,
+    PieceType::Knight,
+    PieceType::Lance,
+    PieceType::Pawn,
+];
+
+#[derive(Debug)]
+pub struct KifWriter {
+    engine_options: Vec<cli::EngineOptions>,
+    engine_names: Vec<String>,
+    options: cli::KifOutOptions,
+    meta: cli::MetaDataOptions,
+}
+
+impl KifWriter {
+    pub fn new(
+        options: &cli::KifOutOptions,
+        meta: &cli::MetaDataOptions,
+        engine_options: Vec<cli::EngineOptions>,
+        engine_names: Vec<String>,
+    ) -> Result<KifWriter, Error> {
+        let writer = KifWriter {
+            engine_options,
+            engine_names,
+            options: options.clone(),
+            meta: meta.clone(),
+        };
+        // Each game creates its own file, so check for a previous run before the first game
+        let first = writer.game_file_name(1);
+        if Path::new(&first).exists() {
+            return Err(Error::new(
+                ErrorKind::AlreadyExists,
+                format!("KIF output file {first} already exists"),
+            ));
+        }
+        Ok(writer)
+    }
+
+    // KIF holds a single game per file, so "games.kif" becomes "games-1.kif", "games-2.kif", ...
+    fn game_file_name(&self, game_number: u64) -> String {
+        util::numbered_file_name(&self.options.file, game_number)
+    }
+
+    pub fn write(&mut self, match_result: &tournament::MatchResult) -> Result<(), Error> {
+        let f = &mut File::create_new(self.game_file_name(match_result.ticket.id + 1))?;
+        let ticket = &match_result.ticket;
+        let opening = ticket.opening;
+
+        writeln!(f, "#KIF version=2.0 encoding=UTF-8")?;
+        writeln!(f, "# ---- Shogitest 棋譜ファイル ----")?;
+        writeln!(
+            f,
+            "開始日時：{}",
+            match_result
+                .game_start
+                .with_timezone(&chrono::Local)
+                .format("%Y/%m/%d %H:%M:%S")
+        )?;
+        writeln!(f, "棋戦：{}", self.meta.event_name)?;
+        writeln!(f, "場所：{}", self.meta.site_name)?;
+        let sente_tc = self.engine_options[ticket.engines[0]].time_control;
+        let gote_tc = self.engine_options[ticket.engines[1]].time_control;
+        if sente_tc == gote_tc {
+            writeln!(f, "持ち時間：{sente_tc}")?;
+        }
+        let handicap = Handicap::detect(&opening);
+        if opening.is_clone_of(&Position::default()) {
+            writeln!(f, "手合割：平手")?;
+        } else if let Some(handicap) = handicap {
+            writeln!(f, "手合割：{}", handicap.to_japanese())?;
+        } else {
+            write_board(f, &opening)?;
+        }
+        // Handicap games name the players 下手 (receiver) and 上手 (giver)
+        let (sente_label, gote_label) = match handicap {
+            Some(_) => ("下手", "上手"),
+            None => ("先手", "後手"),
+        };
+        writeln!(f, "{sente_label}：{}", self.engine_names[ticket.engines[0]])?;
+        writeln!(f, "{gote_label}：{}", self.engine_names[ticket.engines[1]])?;
+
+        let outcome = match_result.outcome;
+        let played = match self.options.format {
+            cli::KifFormat::Kif => Self::write_kif_moves(f, match_result)?,
+            cli::KifFormat::Ki2 => Self::write_ki2_moves(f, match_result)?,
+        };
+        if matches!(
+            outcome,
+            GameOutcome::DrawByAdjudication
+                | GameOutcome::WinByAdjudication(_)
+                | GameOutcome::WinByForcedMate(_)
+                | GameOutcome::LossByDisconnection(_)
+        ) {
+            writeln!(f, "*{}", outcome.to_string())?;
+        }
+
+        match outcome.winner() {
+            Some(Color::Sente) => writeln!(f, "まで{played}手で{sente_label}の勝ち")?,
+            Some(Color::Gote) => writeln!(f, "まで{played}手で{gote_label}の勝ち")?,
+            None if matches!(
+                outcome,
+                GameOutcome::DrawBySennichite
+                    | GameOutcome::DrawByPerpetual(_)
+                    | GameOutcome::DrawByRepetitionAdjudication
+            ) =>
+            {
+                writeln!(f, "まで{played}手で千日手")?
+            }
+            None if matches!(
+                outcome,
+                GameOutcome::DrawByMoveLimit | GameOutcome::DrawByImpasse
+            ) =>
+            {
+                writeln!(f, "まで{played}手で持将棋")?
+            }
+            None => writeln!(f, "まで{played}手で中断")?,
+        }
+
+        Ok(())
+    }
+
+    // Returns the number of moves played.
+    fn write_kif_moves(
+        f: &mut File,
+        match_result: &tournament::MatchResult,
+    ) -> Result<usize, Error> {
+        let opening = match_result.ticket.opening;
+        writeln!(f, "手数----指手---------消費時間--")?;
+
+        let mut position = opening;
+        let mut move_number = opening.ply();
+        let mut last_to: Option<Square> = None;
+        let mut total_time = [Duration::ZERO; 2];
+        let mut special_time = Duration::ZERO;
+
+        for record in &match_result.moves {
+            let stm = position.stm();
+            let Some(next_position) = position.do_move(record.m) else {
+                special_time = record.measured_time;
+                break;
+            };
+            total_time[stm.to_index()] += record.measured_time;
+            let text = move_to_kif(&position, record.m, last_to);
+            write_move_line(
+                f,
+                move_number,
+                &text,
+                record.measured_time,
+                total_time[stm.to_index()],
+            )?;
+            if let Move::Normal { to, .. } | Move::Drop(_, to) = record.m {
+                last_to = Some(to);
+            }
+            position = next_position;
+            move_number += 1;
+        }
+
+        let outcome = match_result.outcome;
+        let stm = position.stm();
+        total_time[stm.to_index()] += special_time;
+        write_move_line(
+            f,
+            move_number,
+            special_move(outcome, stm),
+            special_time,
+            total_time[stm.to_index()],
+        )?;
+
+        Ok(move_number - opening.ply())
+    }
+
+    // KI2 lists moves without times or source squares, several to a line. The terminating
+    // special move is implied by the final "まで" line.
+    fn write_ki2_moves(
+        f: &mut File,
+        match_result: &tournament::MatchResult,
+    ) -> Result<usize, Error> {
+        const MOVES_PER_LINE: usize = 6;
+
+        let mut position = match_result.ticket.opening;
+        let mut last_to: Option<Square> = None;
+        let mut line = String::new();
+        let mut played = 0;
+
+        for record in &match_result.moves {
+            let Some(next_position) = position.do_move(record.m) else {
+                break;
+            };
+            let text = position.format_ki2_move(record.m, last_to);
+            if played % MOVES_PER_LINE != 0 {
+                // Full-width characters take up two columns
+                let width: usize = line.chars().map(|c| if c.is_ascii() { 1 } else { 2 }).sum();
+                let column = (played % MOVES_PER_LINE) * 14;
+                line += &" ".repeat(column.saturating_sub(width).max(1));
+            }
+            line += &text;
+            played += 1;
+            if played % MOVES_PER_LINE == 0 {
+                writeln!(f, "{line}")?;
+                line.clear();
+            }
+            if let Move::Normal { to, .. } | Move::Drop(_, to) = record.m {
+                last_to = Some(to);
+            }
+            position = next_position;
+        }
+        if !line.is_empty() {
+            writeln!(f, "{line}")?;
+        }
+
+        Ok(played)
+    }
+}
+
+/// A game read back from a file written by `KifWriter`.
+#[derive(Debug, Clone)]
+pub struct KifGame {
+    pub start: Position,
+    pub moves: Vec<Move>,
+}
+
+/// Reads the game in the text of a KIF or KI2 file written by `KifWriter`. Each move is matched
+/// against the legal moves of the position it is played in, and the game ends at the terminating
+/// special move or the final "まで" line.
+pub fn read_game(text: &str) -> Result<KifGame, String> {
+    let mut start = Position::default();
+    let mut board: Vec<&str> = vec![];
+    let mut hands = ["", ""];
+    let mut stm = Color::Sente;
+    let mut ply = 1;
+    let mut texts: Vec<&str> = vec![];
+
+    for line in text.lines() {
+        let line = line.trim_end();
+        if line.starts_with('#') || line.starts_with('*') || line.starts_with("手数--") {
+            continue;
+        } else if line.starts_with("まで") {
+            break;
+        } else if let Some(handicap) = line.strip_prefix("手合割：") {
+            start = match handicap {
+                "平手" => Position::default(),
+                _ => Handicap::ALL
+                    .into_iter()
+                    .find(|h| h.to_japanese() == handicap)
+                    .map(Position::handicap)
+                    .ok_or_else(|| format!("unknown handicap {handicap}"))?,
+            };
+        } else if let Some(hand) = line.strip_prefix("先手の持駒：") {
+            hands[Color::Sente.to_index()] = hand;
+        } else if let Some(hand) = line.strip_prefix("後手の持駒：") {
+            hands[Color::Gote.to_index()] = hand;
+        } else if line.starts_with('|') {
+            board.push(line);
+        } else if line == "後手番" {
+            stm = Color::Gote;
+        } else if let Some(played) = line.strip_prefix("手数＝") {
+            ply = played
+                .parse::<usize>()
+                .map_err(|_| format!("invalid line {line}"))?
+                + 1;
+        } else if line.starts_with(['▲', '△']) {
+            // KI2: several moves to a line, each starting with its side's mark
+            texts.extend(
+                line.split_inclusive(' ')
+                    .map(|text| text.trim_end_matches(' '))
+                    .filter(|text| !text.is_empty()),
+            );
+        } else if let Some((number, text)) = line.trim_start().split_once(' ')
+            && number.parse::<usize>().is_ok()
+        {
+            // KIF: the move number, the move text and the times, separated by spaces
+            texts.push(text.split(' ').next().unwrap_or(""));
+        }
+    }
+
+    if !board.is_empty() {
+        start = parse_board(&board, hands, stm, ply)?;
+    }
+
+    let mut moves = vec![];
+    let mut position = start;
+    let mut last_to: Option<Square> = None;
+    for text in texts {
+        if SPECIAL_MOVES.contains(&text) {
+            break;
+        }
+        let m = position
+            .legal_moves()
+            .iter()
+            .copied()
+            .find(|&m| {
+                move_to_kif(&position, m, last_to) == text
+                    || position.format_ki2_move(m, last_to) == text
+            })
+            .ok_or_else(|| format!("move {}: illegal move {text}", moves.len() + 1))?;
+        position = position.do_move(m).unwrap();
+        if let Move::Normal { to, .. } | Move::Drop(_, to) = m {
+            last_to = Some(to);
+        }
+        moves.push(m);
+    }
+    Ok(KifGame { start, moves })
+}
+
+// Reads a board diagram written by `write_board`, with the hands and the side to move found
+// around it.
+fn parse_board(
+    rows: &[&str],
+    hands: [&str; 2],
+    stm: Color,
+    ply: usize,
+) -> Result<Position, String> {
+    let mut sfen_rows = vec![];
+    for row in rows {
+        // "|" followed by a colour prefix and a piece for each of the nine files
+        let cells: Vec<char> = row.chars().skip(1).take(18).collect();
+        let mut sfen_row = String::new();
+        let mut empty = 0;
+        for cell in cells.chunks(2) {
+            let piece = match cell {
+                [_, '・'] => None,
+                [prefix @ (' ' | 'v'), piece] => Some(
+                    BOARD_PTYPES
+                        .into_iter()
+                        .find(|pt| {
+                            pt.to_kanji_char() == *piece
+                                || (*piece == '王' && *pt == PieceType::King)
+                        })
+                        .map(|pt| {
+                            (
+                                if *prefix == 'v' {
+                                    Color::Gote
+                                } else {
+                                    Color::Sente
+                                },
+                                pt,
+                            )
+                        })
+                        .ok_or_else(|| format!("invalid board row {row}"))?,
+                ),
+                _ => return Err(format!("invalid board row {row}")),
+            };
+            match piece {
+                None => empty += 1,
+                Some((color, pt)) => {
+                    if empty > 0 {
+                        sfen_row += &empty.to_string();
+                        empty = 0;
+                    }
+                    sfen_row += pt.to_str(color);
+                }
+            }
+        }
+        if empty > 0 {
+            sfen_row += &empty.to_string();
+        }
+        sfen_rows.push(sfen_row);
+    }
+
+    let mut sfen_hand = String::new();
+    for color in [Color::Sente, Color::Gote] {
+        let hand = hands[color.to_index()];
+        for piece in hand
+            .split('　')
+            .filter(|piece| !piece.is_empty() && *piece != "なし")
+        {
+            let mut chars = piece.chars();
+            let pt = chars
+                .next()
+                .and_then(|c| HAND_ORDER.into_iter().find(|pt| pt.to_kanji_char() == c))
+                .ok_or_else(|| format!("invalid hand {hand}"))?;
+            let count = match chars.as_str() {
+                "" => 1,
+                number => (1..=18)
+                    .find(|&n| kanji_number(n) == number)
+                    .ok_or_else(|| format!("invalid hand {hand}"))?,
+            };
+            if count > 1 {
+                sfen_hand += &count.to_string();
+            }
+            sfen_hand += pt.to_str(color);
+        }
+    }
+    if sfen_hand.is_empty() {
+        sfen_hand = String::from("-");
+    }
+
+    let color = match stm {
+        Color::Sente => "b",
+        Color::Gote => "w",
+    };
+    let sfen = format!("{} {color} {sfen_hand} {ply}", sfen_rows.join("/"));
+    Position::parse(&sfen).ok_or_else(|| format!("invalid board {sfen}"))
+}
+
+fn kanji_number(n: u8) -> String {
+    const DIGITS: [&str; 10] = ["", "一", "二", "三", "四", "五", "六", "七", "八", "九"];
+    if n < 10 {
+        DIGITS[n as usize].to_string()
+    } else {
+        format!("十{}", DIGITS[(n % 10) as usize])
+    }
+}
+
+fn write_hand(f: &mut File, position: &Position, color: Color) -> Result<(), Error> {
+    let label = match color {
+        Color::Sente => "先手",
+        Color::Gote => "後手",
+    };
+    let hand = position.hand(color);
+    let mut pieces = String::new();
+    for pt in HAND_ORDER {
+        let count = hand.get(pt);
+        if count > 0 {
+            let count = if count == 1 {
+                String::new()
+            } else {
+                kanji_number(count)
+            };
+            pieces += &format!("{}{count}　", pt.to_kanji());
+        }
+    }
+    if pieces.is_empty() {
+        pieces = String::from("なし");
+    }
+    writeln!(f, "{label}の持駒：{pieces}")
+}
+
+// Board diagram (BOD) for games that do not start from the standard position.
+fn write_board(f: &mut File, position: &Position) -> Result<(), Error> {
+    const RANKS: [char; 9] = ['一', '二', '三', '四', '五', '六', '七', '八', '九'];
+
+    write_hand(f, position, Color::Gote)?;
+    writeln!(f, "  ９ ８ ７ ６ ５ ４ ３ ２ １")?;
+    writeln!(f, "+---------------------------+")?;
+    for (rank, rank_char) in RANKS.iter().enumerate() {
+        let mut line = String::from("|");
+        for file in (0..9).rev() {
+            let sq = Square::new(file, rank as i8).unwrap();
+            let (prefix, piece) = match position.piece_at(sq) {
+                Some((Color::Gote, pt)) => ('v', pt.to_kanji_char()),
+                Some((Color::Sente, pt)) => (' ', pt.to_kanji_char()),
+                None => (' ', PieceType::None.to_kanji_char()),
+            };
+            line.push(prefix);
+            line.push(piece);
+        }
+        writeln!(f, "{line}|{rank_char}")?;
+    }
+    writeln!(f, "+---------------------------+")?;
+    write_hand(f, position, Color::Sente)?;
+    if position.stm() == Color::Gote {
+        writeln!(f, "後手番")?;
+    }
+    if position.ply() > 1 {
+        writeln!(f, "手数＝{}", position.ply() - 1)?;
+    }
+    Ok(())
+}
+
+/// KIF move text, e.g. "７六歩(77)", "同　角成(88)" or "５五角打".
+fn move_to_kif(position: &Position, m: Move, last_to: Option<Square>) -> String {
+    match m {
+        Move::Drop(pt, to) => format!("{}{}打", to.to_japanese(), pt.to_kanji()),
+        Move::Normal { from, to, promo } => {
+            let (color, pt) = position.piece_at(from).unwrap();
+            let destination = if last_to == Some(to) {
+                String::from("同　")
+            } else {
+                to.to_japanese()
+            };
+            let promotion = if promo {
+                "成"
+            } else if pt.promotable()
+                && (position.is_promo_square(from, color) || position.is_promo_square(to, color))
+            {
+                "不成"
+            } else {
+                ""
+            };
+            format!(
+                "{destination}{}{promotion}({}{})",
+                pt.to_kanji(),
+                from.file() + 1,
+                from.rank() + 1
+            )
+        }
+        Move::None | Move::Win | Move::Resign => String::new(),
+    }
+}
+
+// The special move that terminates the record, from the viewpoint of the side to move.
+fn special_move(outcome: GameOutcome, stm: Color) -> &'static str {
+    match outcome {
+        GameOutcome::Undetermined => "中断",
+        GameOutcome::Checkmated(_) => "詰み",
+        GameOutcome::WinInImpasse(_) => "入玉勝ち",
+        GameOutcome::DrawBySennichite
+        | GameOutcome::LossBySennichite(_)
+        | GameOutcome::DrawByPerpetual(_)
+        | GameOutcome::DrawByRepetitionAdjudication => "千日手",
+        GameOutcome::LossByPerpetual(color) | GameOutcome::LossByIllegal(color) => {
+            if color == stm {
+                "反則負け"
+            } else {
+                "反則勝ち"
+            }
+        }
+        GameOutcome::Resignation(_) => "投了",
+        GameOutcome::LossByClock(_) => "切れ負け",
+        GameOutcome::LossByDisconnection(_) => "中断",
+        GameOutcome::DrawByMoveLimit | GameOutcome::DrawByImpasse => "持将棋",
+        GameOutcome::DrawByAdjudication => "中断",
+        GameOutcome::WinByAdjudication(_) | GameOutcome::WinByForcedMate(_) => "中断",
+    }
+}
+
+fn write_move_line(
+    f: &mut File,
+    move_number: usize,
+    text: &str,
+    time: Duration,
+    total: Duration,
+) -> Result<(), Error> {
+    // Full-width characters take up two columns
+    let width: usize = text.chars().map(|c| if c.is_ascii() { 1 } else { 2 }).sum();
+    let padding = " ".repeat(14usize.saturating_sub(width));
+    let time = time.as_secs();
+    let total = total.as_secs();
+    writeln!(
+        f,
+        "{move_number:>4} {text}{padding}({:>2}:{:02}/{:02}:{:02}:{:02})",
+        time / 60,
+        time % 60,
+        total / 3600,
+        total / 60 % 60,
+        total % 60
+    )
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn kif_move_text() {
+        let position = Position::default();
+        let m = Move::parse("7g7f").unwrap();
+        assert_eq!("７六歩(77)", move_to_kif(&position, m, None));
+
+        let position = position.do_move(m).unwrap();
+        let position = position.do_move(Move::parse("3c3d").unwrap()).unwrap();
+        let m = Move::parse("8h2b+").unwrap();
+        assert_eq!("２二角成(88)", move_to_kif(&position, m, None));
+
+        let position = position.do_move(m).unwrap();
+        let sq = Square::parse(b'2', b'b');
+        let m = Move::parse("3a2b").unwrap();
+        assert_eq!("同　銀(31)", move_to_kif(&position, m, sq));
+
+        let position = position.do_move(m).unwrap();
+        let m = Move::parse("B*4e").unwrap();
+        assert_eq!("４五角打", move_to_kif(&position, m, sq));
+    }
+
+    #[test]
+    fn read_written_games() {
+        let dir = std::env::temp_dir().join(format!("shogitest-kifread-{}", std::process::id()));
+        std::fs::create_dir_all(&dir).unwrap();
+        let meta = cli::MetaDataOptions {
+            event_name: String::from("?"),
+            site_name: String::from("?"),
+        };
+        let games = [
+            (Position::default(), "7g7f 3c3d 8h2b+ 3a2b B*4e"),
+            (Position::handicap(Handicap::TwoPiece), "3c3d 7g7f"),
+            (
+                Position::parse("4k4/9/9/9/9/9/9/9/4K4 w G2Pr 5").unwrap(),
+                "R*5b 5i4h",
+            ),
+        ];
+
+        for format in [cli::KifFormat::Kif, cli::KifFormat::Ki2] {
+            let options = cli::KifOutOptions {
+                file: dir
+                    .join(format!("{format:?}.kif"))
+                    .to_string_lossy()
+                    .to_string(),
+                format,
+            };
+            let engine_options = vec![cli::EngineOptions::default(); 2];
+            let names = vec![String::from("a"), String::from("b")];
+            let mut writer = KifWriter::new(&options, &meta, engine_options, names).unwrap();
+            for (id, (opening, moves)) in games.iter().enumerate() {
+                let moves: Vec<Move> = moves.split(' ').map(|m| Move::parse(m).unwrap()).collect();
+                let match_result = tournament::MatchResult {
+                    ticket: tournament::MatchTicket {
+                        id: id as u64,
+                        opening: *opening,
+                        opening_source: 0,
+                        engines: [0, 1],
+                    },
+                    game_start: chrono::Utc::now(),
+                    outcome: GameOutcome::Resignation(Color::Sente),
+                    moves: moves
+                        .iter()
+                        .map(|&m| crate::engine::MoveRecord {
+                            m,
+                            mstr: m.to_string(),
+                            ..crate::engine::MoveRecord::default()
+                        })
+                        .collect(),
+                };
+                writer.write(&match_result).unwrap();
+
+                let text = std::fs::read_to_string(writer.game_file_name(id as u64 + 1)).unwrap();
+                assert!(text.starts_with("#KIF version=2.0 encoding=UTF-8\n"));
+                let game = read_game(&text).unwrap();
+                assert_eq!(*opening, game.start, "{format:?} {id}");
+                assert_eq!(moves, game.moves, "{format:?} {id}");
+            }
+        }
+        std::fs::remove_dir_all(&dir).unwrap();
+
+        let text = "手合割：平手\n   1 ７六歩(77)   ( 0:00/00:00:00)\n   2 ７六歩(77)   ( 0:00/00:00:00)\n";
+        assert_eq!(
+            "move 2: illegal move ７六歩(77)",
+            read_game(text).unwrap_err()
+        );
+    }
+
+    #[test]
+    fn refuse_existing_output() {
+        let dir = std::env::temp_dir().join(format!("shogitest-kif-{}", std::process::id()));
+        std::fs::create_dir_all(&dir).unwrap();
+        let options = cli::KifOutOptions {
+            file: dir.join("games.kif").to_string_lossy().to_string(),
+            ..cli::KifOutOptions::default()
+        };
+        let meta = cli::MetaDataOptions {
+            event_name: String::from("?"),
+            site_name: String::from("?"),
+        };
+        assert!(KifWriter::new(&options, &meta, vec![], vec![]).is_ok());
+
+        std::fs::write(dir.join("games-1.kif"), "").unwrap();
+        let err = KifWriter::new(&options, &meta, vec![], vec![]).unwrap_err();
+        assert_eq!(ErrorKind::AlreadyExists, err.kind());
+        std::fs::remove_dir_all(&dir).unwrap();
+    }
+}
@@ -0,0 +1,4 @@
+//! Shogi rules, move generation and game record formats used by the shogitest tournament
+//! runner, available for other tools to build on.
+
+pub mod shogi;
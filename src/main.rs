@@ -1,16 +1,21 @@
 #![feature(duration_constants)]
-#![feature(if_let_guard)]
 #![feature(str_split_whitespace_remainder)]
 
 use log::info;
 use rand::SeedableRng;
+use shogitest::shogi;
 
 mod book;
 mod cli;
+mod csa;
 mod engine;
+mod evalbook;
+mod gamebook;
+mod genbook;
+mod kif;
+mod perft;
 mod pgn;
 mod runner;
-mod shogi;
 mod sprt;
 mod stats;
 mod tc;
@@ -20,29 +25,81 @@ mod util;
 fn main() -> std::io::Result<()> {
     flexi_logger::Logger::try_with_env().unwrap().start().ok();
 
+    if std::env::args().nth(1).as_deref() == Some("perft") {
+        if let Some(perft_options) = cli::parse_perft() {
+            perft::run(&perft_options)?;
+        }
+        return Ok(());
+    }
+
+    if std::env::args().nth(1).as_deref() == Some("evalbook") {
+        if let Some(evalbook_options) = cli::parse_evalbook() {
+            evalbook::run(&evalbook_options)?;
+        }
+        return Ok(());
+    }
+
+    if std::env::args().nth(1).as_deref() == Some("gamebook") {
+        if let Some(gamebook_options) = cli::parse_gamebook() {
+            gamebook::run(&gamebook_options)?;
+        }
+        return Ok(());
+    }
+
+    if std::env::args().nth(1).as_deref() == Some("genbook") {
+        if let Some(genbook_options) = cli::parse_genbook() {
+            genbook::run(&genbook_options)?;
+        }
+        return Ok(());
+    }
+
     let Some(cli_options) = cli::parse() else {
         return Ok(());
     };
-    info!("{:#?}", &cli_options);
+    info!("{:#?}", cli_options);
+
+    if cli_options.list_options {
+        for engine in &cli_options.engines {
+            // Options are not sent, so that they can be listed even when misspelt
+            let builder = engine::EngineBuilder {
+                usi_options: vec![],
+                ..engine.builder.clone()
+            };
+            let engine = builder.init()?;
+            println!("Options of {}:", engine.name());
+            for option in &engine.info().options {
+                println!("  {option}");
+            }
+        }
+        return Ok(());
+    }
 
     if cli_options.engines.len() < 2 {
         eprintln!("We require at least two engines to be supplied.");
         return Ok(());
     }
 
-    if cli_options.book.is_none() {
+    if cli_options.books.is_empty() {
         eprintln!("Openings file required.");
         return Ok(());
     }
 
-    let engine_names = cli_options.engine_names();
+    let Some(engine_names) = cli_options.engine_names() else {
+        return Ok(());
+    };
 
     let opening_book = {
         let mut rng = match cli_options.rand_seed {
             Some(seed) => rand_chacha::ChaCha8Rng::seed_from_u64(seed),
             None => rand_chacha::ChaCha8Rng::from_os_rng(),
         };
-        book::OpeningBook::new(cli_options.book.as_ref().unwrap(), &mut rng).unwrap()
+        book::OpeningBook::new(
+            &cli_options.books,
+            cli_options.variant,
+            cli_options.rules,
+            &mut rng,
+        )
+        .unwrap()
     };
 
     let mut tournament: Box<dyn tournament::Tournament> =
@@ -58,6 +115,25 @@ fn main() -> std::io::Result<()> {
         )?);
     }
 
+    if let Some(kif) = cli_options.kif {
+        tournament = Box::new(tournament::KifOutWrapper::new(
+            tournament,
+            &kif,
+            &cli_options.meta,
+            cli_options.engines.clone(),
+            engine_names.clone(),
+        )?);
+    }
+
+    if let Some(csa) = cli_options.csa {
+        tournament = Box::new(tournament::CsaOutWrapper::new(
+            tournament,
+            &csa,
+            &cli_options.meta,
+            engine_names.clone(),
+        )?);
+    }
+
     let sprt_parameters = cli_options
         .sprt
         .map(|sprt| sprt::SprtParameters::new(sprt.nelo0, sprt.nelo1, sprt.alpha, sprt.beta));
@@ -66,7 +142,14 @@ fn main() -> std::io::Result<()> {
         tournament,
         engine_names.clone(),
         cli_options.engines.clone(),
-        cli_options.book.map(|b| b.file.clone()),
+        cli_options
+            .books
+            .iter()
+            .map(|b| match b.handicap {
+                Some(handicap) => handicap.name().to_string(),
+                None => b.file.clone(),
+            })
+            .collect(),
         sprt_parameters,
     ));
 
@@ -79,6 +162,7 @@ fn main() -> std::io::Result<()> {
         cli_options.engines,
         cli_options.concurrency,
         cli_options.adjudication,
+        cli_options.rules,
         cli_options.report_interval,
     );
     r.run(tournament);
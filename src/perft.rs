@@ -0,0 +1,115 @@
+use crate::cli;
+use crate::engine::EngineResult;
+use crate::shogi::{Move, Position};
+use std::sync::Mutex;
+use std::time::Instant;
+
+// Divide, with the root moves shared out between `threads` threads.
+fn divide(position: &Position, depth: usize, threads: usize) -> Vec<(Move, u64)> {
+    let moves = position.legal_moves();
+    let next = Mutex::new(0);
+    let results = Mutex::new(Vec::with_capacity(moves.len()));
+
+    std::thread::scope(|s| {
+        for _ in 0..threads {
+            s.spawn(|| {
+                loop {
+                    let m = {
+                        let mut next = next.lock().unwrap();
+                        let Some(&m) = moves.get(*next) else { break };
+                        *next += 1;
+                        m
+                    };
+                    let count = position.do_move(m).unwrap().perft(depth - 1);
+                    results.lock().unwrap().push((m, count));
+                }
+            });
+        }
+    });
+
+    let mut results = results.into_inner().unwrap();
+    results.sort_by_key(|&(m, _)| moves.iter().position(|&other| other == m));
+    results
+}
+
+pub fn run(options: &cli::PerftOptions) -> std::io::Result<()> {
+    let start = Instant::now();
+    let results = divide(&options.position, options.depth, options.threads);
+    let elapsed = start.elapsed();
+    let nodes: u64 = results.iter().map(|&(_, count)| count).sum();
+
+    if options.divide {
+        for (m, count) in &results {
+            println!("{m}: {count}");
+        }
+        println!();
+    }
+    println!("Nodes searched: {nodes}");
+    println!(
+        "Time: {}ms, NPS: {}",
+        elapsed.as_millis(),
+        (nodes as f64 / elapsed.as_secs_f64().max(1e-9)) as u64
+    );
+
+    let Some(builder) = &options.engine else {
+        return Ok(());
+    };
+
+    let mut engine = builder.init()?;
+    engine.isready()?;
+    let engine_results = match engine.divide(&options.position, options.depth) {
+        EngineResult::Ok(counts) => counts,
+        EngineResult::Err(err) => return Err(err),
+        EngineResult::Timeout | EngineResult::Disconnected => {
+            eprintln!("Engine {} did not finish perft", engine.name());
+            return Ok(());
+        }
+    };
+
+    println!();
+    let mut differences = 0;
+    for (m, count) in &results {
+        let mstr = m.to_string();
+        match engine_results.iter().find(|(other, _)| *other == mstr) {
+            Some((_, engine_count)) if engine_count == count => {}
+            Some((_, engine_count)) => {
+                println!("{mstr}: expected {count}, engine reports {engine_count}");
+                differences += 1;
+            }
+            None => {
+                println!("{mstr}: missing from engine output");
+                differences += 1;
+            }
+        }
+    }
+    for (mstr, engine_count) in &engine_results {
+        if !results.iter().any(|(m, _)| m.to_string() == *mstr) {
+            println!("{mstr}: not a legal move, engine reports {engine_count}");
+            differences += 1;
+        }
+    }
+    if differences == 0 {
+        println!(
+            "Engine {} agrees on all {} moves",
+            engine.name(),
+            results.len()
+        );
+    }
+
+    Ok(())
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn threaded_divide_matches_perft() {
+        let position = Position::default();
+        for threads in [1, 3] {
+            let results = divide(&position, 3, threads);
+            assert_eq!(position.divide(3), results);
+            assert_eq!(25470, results.iter().map(|&(_, count)| count).sum::<u64>());
+        }
+    }
+}
@@ -2,6 +2,93 @@ use crate::{cli, engine::Score, shogi, tournament};
 use std::fs::File;
 use std::io::{Error, Write};
 
+/// A game read back from a file written by `PgnWriter`.
+#[derive(Debug, Clone)]
+pub struct PgnGame {
+    pub start: shogi::Position,
+    /// Each move as written, with the score from the move's comment.
+    pub moves: Vec<(String, Score)>,
+}
+
+// Parses the score at the start of a move comment, as written by `PgnWriter::write`.
+fn parse_score(comment: &str) -> Score {
+    let token = comment.split([' ', ',']).next().unwrap_or("");
+    if let Some(mate) = token.strip_prefix("+M") {
+        mate.parse().map_or(Score::None, Score::Mate)
+    } else if let Some(mate) = token.strip_prefix("-M") {
+        mate.parse().map_or(Score::None, |x: i32| Score::Mate(-x))
+    } else if let Ok(pawns) = token.parse::<f64>() {
+        Score::Cp((pawns * 100.0).round() as i32)
+    } else {
+        Score::None
+    }
+}
+
+/// Reads every game in the text of a PGN file. Games with an invalid `FEN` header are skipped.
+pub fn read_games(text: &str) -> Vec<PgnGame> {
+    let mut games = vec![];
+    let mut headers: Vec<(&str, &str)> = vec![];
+    let mut moves = vec![];
+
+    let mut finish = |headers: &mut Vec<(&str, &str)>, moves: &mut Vec<(String, Score)>| {
+        let header = |key| headers.iter().find(|(k, _)| *k == key).map(|(_, v)| *v);
+        let start = match (
+            header("FEN"),
+            header("Variant").and_then(shogi::Variant::parse),
+        ) {
+            (Some(sfen), _) => shogi::Position::parse(sfen),
+            (None, Some(variant)) => Some(variant.startpos()),
+            (None, None) => Some(shogi::Position::default()),
+        };
+        if let Some(start) = start
+            && (!headers.is_empty() || !moves.is_empty())
+        {
+            games.push(PgnGame {
+                start,
+                moves: std::mem::take(moves),
+            });
+        }
+        headers.clear();
+        moves.clear();
+    };
+
+    for line in text.lines().map(str::trim) {
+        if let Some(header) = line.strip_prefix('[').and_then(|l| l.strip_suffix(']')) {
+            if !moves.is_empty() {
+                finish(&mut headers, &mut moves);
+            }
+            if let Some((key, value)) = header.split_once(' ') {
+                headers.push((key, value.trim_matches('"')));
+            }
+        } else if matches!(line, "1-0" | "0-1" | "1/2-1/2" | "undetermined") {
+            finish(&mut headers, &mut moves);
+        } else if !line.is_empty() {
+            let (mstr, comment) = line.split_once(" {").unwrap_or((line, ""));
+            moves.push((mstr.to_string(), parse_score(comment)));
+        }
+    }
+    if !headers.is_empty() || !moves.is_empty() {
+        finish(&mut headers, &mut moves);
+    }
+    games
+}
+
+/// Reads a move as written by `PgnWriter` in any notation. Western and Japanese move text is
+/// matched against the legal moves of `position`; `last_to` is the destination of the previous
+/// move, which Japanese notation refers to with 同.
+pub fn parse_move(
+    position: &shogi::Position,
+    mstr: &str,
+    last_to: Option<shogi::Square>,
+) -> Option<shogi::Move> {
+    if let Some(m) = shogi::Move::parse(mstr) {
+        return Some(m);
+    }
+    position.legal_moves().iter().copied().find(|&m| {
+        position.format_western_move(m) == mstr || position.format_ki2_move(m, last_to) == mstr
+    })
+}
+
 #[derive(Debug)]
 pub struct PgnWriter {
     file: File,
@@ -52,12 +139,20 @@ impl PgnWriter {
         Self::write_header(f, "White", &self.engine_names[ticket.engines[1]])?;
         Self::write_header(f, "Gote", &self.engine_names[ticket.engines[1]])?;
         Self::write_header(f, "Result", result_str)?;
+        let variant = match_result.ticket.opening.variant();
+        if variant != shogi::Variant::Standard {
+            Self::write_header(f, "Variant", variant.name())?;
+        }
         if match_result.ticket.opening != shogi::Position::default() {
             Self::write_header(f, "FEN", &match_result.ticket.opening.to_string())?;
             Self::write_header(f, "SetUp", "1")?;
         }
         Self::write_header(f, "PlyCount", &match_result.moves.len().to_string())?;
-        Self::write_header(f, "Termination", match_result.outcome.to_pgn_termination_string())?;
+        Self::write_header(
+            f,
+            "Termination",
+            match_result.outcome.to_pgn_termination_string(),
+        )?;
         Self::write_header(f, "GameStartTime", &match_result.game_start.to_rfc3339())?;
         Self::write_header(
             f,
@@ -76,12 +171,28 @@ impl PgnWriter {
 
         writeln!(f)?;
 
+        let mut position = match_result.ticket.opening;
+        let mut last_to: Option<shogi::Square> = None;
         for (i, m) in match_result.moves.iter().enumerate() {
+            let next_position = position.do_move(m.m);
             let mstr = if m.mstr.is_empty() {
-                "output-was-empty"
+                String::from("output-was-empty")
+            } else if next_position.is_none() {
+                // Illegal moves are kept as the engine sent them
+                m.mstr.clone()
             } else {
-                &m.mstr
+                match self.options.notation {
+                    cli::MoveNotation::Usi => m.mstr.clone(),
+                    cli::MoveNotation::Western => position.format_western_move(m.m),
+                    cli::MoveNotation::Japanese => position.format_ki2_move(m.m, last_to),
+                }
             };
+            if let Some(next_position) = next_position {
+                position = next_position;
+            }
+            if let shogi::Move::Normal { to, .. } | shogi::Move::Drop(_, to) = m.m {
+                last_to = Some(to);
+            }
             let score_str = match m.score {
                 Score::None => String::from("none"),
                 Score::Cp(cp) => format!("{:+.2}", cp as f64 / 100.0),
@@ -124,3 +235,61 @@ impl PgnWriter {
         Ok(())
     }
 }
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn read_written_games() {
+        let text = r#"[Event "?"]
+[Result "1-0"]
+[FEN "lnsgkgsnl/1r5b1/ppppppppp/9/9/2P6/PP1PPPPPP/1B5R1/LNSGKGSNL w - 2"]
+[SetUp "1"]
+
+3c3d {-0.35 12 t=0.1s}
+8h2b+ {+M3 5 t=0.1s}
+resign {none 0 t=0s, Gote resigns}
+1-0
+
+[Event "?"]
+[Variant "minishogi"]
+
+2e2d {+1.20 8 t=0.1s}
+1/2-1/2
+"#;
+        let games = read_games(text);
+        assert_eq!(2, games.len());
+        assert_eq!(
+            "lnsgkgsnl/1r5b1/ppppppppp/9/9/2P6/PP1PPPPPP/1B5R1/LNSGKGSNL w - 2",
+            games[0].start.to_string()
+        );
+        let moves: Vec<&str> = games[0].moves.iter().map(|(m, _)| m.as_str()).collect();
+        assert_eq!(vec!["3c3d", "8h2b+", "resign"], moves);
+        assert!(matches!(games[0].moves[0].1, Score::Cp(-35)));
+        assert!(matches!(games[0].moves[1].1, Score::Mate(3)));
+        assert!(matches!(games[0].moves[2].1, Score::None));
+        assert_eq!(shogi::Variant::Minishogi.startpos(), games[1].start);
+        assert!(matches!(games[1].moves[0].1, Score::Cp(120)));
+    }
+
+    #[test]
+    fn parse_move_notations() {
+        let position = shogi::Position::default();
+        let m = shogi::Move::parse("7g7f").unwrap();
+        for mstr in ["7g7f", "P-7f", "▲７六歩"] {
+            assert_eq!(Some(m), parse_move(&position, mstr, None), "{mstr}");
+        }
+        assert_eq!(None, parse_move(&position, "P-7e", None));
+
+        let moves = ["7g7f", "3c3d", "8h2b+", "3a2b"];
+        let mut position = shogi::Position::default();
+        for m in moves[..3].iter().map(|m| shogi::Move::parse(m).unwrap()) {
+            position = position.do_move(m).unwrap();
+        }
+        let recapture = shogi::Move::parse(moves[3]).unwrap();
+        let last_to = shogi::Square::parse(b'2', b'b');
+        assert_eq!(Some(recapture), parse_move(&position, "Sx2b", last_to));
+        assert_eq!(Some(recapture), parse_move(&position, "△同　銀", last_to));
+    }
+}
@@ -10,13 +10,14 @@ use crate::{
 use chrono::Utc;
 use log::info;
 use std::thread;
-use std::time::Instant;
+use std::time::{Duration, Instant};
 
 #[derive(Debug)]
 pub struct Runner {
     engines: Vec<cli::EngineOptions>,
     concurrency: u64,
     adjudication: cli::AdjudicationOptions,
+    rules: shogi::GameRules,
     report_interval: Option<u64>,
 }
 
@@ -25,12 +26,14 @@ impl Runner {
         engines: Vec<cli::EngineOptions>,
         concurrency: u64,
         adjudication: cli::AdjudicationOptions,
+        rules: shogi::GameRules,
         report_interval: Option<u64>,
     ) -> Runner {
         Runner {
             engines,
             concurrency,
             adjudication,
+            rules,
             report_interval,
         }
     }
@@ -48,8 +51,9 @@ impl Runner {
             let send_result = send_result.clone();
             let engines = self.engines.clone();
             let adjudication = self.adjudication.clone();
+            let rules = self.rules;
             thread_handles.push(thread::spawn(move || {
-                runner_thread_main(engines, adjudication, i, recv_ticket, send_result);
+                runner_thread_main(engines, adjudication, rules, i, recv_ticket, send_result);
             }));
         }
 
@@ -110,6 +114,7 @@ impl Runner {
 fn runner_thread_main(
     engine_options: Vec<cli::EngineOptions>,
     adjudication: cli::AdjudicationOptions,
+    rules: shogi::GameRules,
     thread_index: u64,
     recv: crossbeam_channel::Receiver<Option<MatchTicket>>,
     send: crossbeam_channel::Sender<MatchResult>,
@@ -121,17 +126,19 @@ fn runner_thread_main(
 
     while let Some(ticket) = recv.recv().unwrap() {
         assert!(ticket.engines[0] != ticket.engines[1]);
-        info!("Thread {thread_index} received ticket: {:?}", &ticket);
+        info!("Thread {thread_index} received ticket: {:?}", ticket);
 
-        let result = run_match(&engine_options, &adjudication, &mut engines, &ticket).unwrap();
+        let result =
+            run_match(&engine_options, &adjudication, rules, &mut engines, &ticket).unwrap();
 
-        info!("Thread {thread_index} sending result: {:?}", &result);
+        info!("Thread {thread_index} sending result: {:?}", result);
         send.send(result).unwrap();
     }
 }
 
 fn do_adjudication(
     stm: shogi::Color,
+    position: &shogi::Position,
     adjudication: &cli::AdjudicationOptions,
     match_result: &mut MatchResult,
 ) {
@@ -209,11 +216,19 @@ fn do_adjudication(
         assert!(Some(stm) == match_result.moves.last().and_then(|m| m.stm));
         match_result.outcome = GameOutcome::WinByAdjudication(!stm);
     }
+
+    // A proven mate for the side to move overrides the score-based adjudications
+    if let Some(ref mate) = adjudication.mate
+        && position.find_mate(mate.plies, mate.nodes).is_some()
+    {
+        match_result.outcome = GameOutcome::WinByForcedMate(!stm);
+    }
 }
 
 fn run_match(
     engine_options: &[cli::EngineOptions],
     adjudication: &cli::AdjudicationOptions,
+    rules: shogi::GameRules,
     engines: &mut [engine::Engine],
     ticket: &MatchTicket,
 ) -> Result<MatchResult, std::io::Error> {
@@ -243,22 +258,55 @@ fn run_match(
         engines[ticket.engines[i]].usinewgame()?;
     }
 
-    let mut game = shogi::Game::new(ticket.opening);
-    loop {
+    let mut game = shogi::Game::with_rules(ticket.opening, rules);
+    // The move each side's engine is pondering on while its opponent thinks
+    let mut pondering: [Option<shogi::Move>; 2] = [None, None];
+    while !match_result.outcome.is_determined() {
         let stm = game.stm();
+        let options = &engine_options[ticket.engines[stm.to_index()]];
         let current_engine = &mut engines[ticket.engines[stm.to_index()]];
 
         let bestmove_timeout = engine_time[stm.to_index()].bestmove_timeout();
 
         // TODO: Improve time measurement here
         let now = Instant::now();
-        current_engine.position(&game)?;
 
-        current_engine.write_line(&format!(
-            "go {}",
-            tc::to_usi_string(stm, &engine_time[0], &engine_time[1])
-        ))?;
-        current_engine.flush()?;
+        let predicted = pondering[stm.to_index()].take();
+        let ponderhit =
+            predicted.is_some() && predicted == match_result.moves.last().map(|mr| mr.m);
+        if predicted.is_some() && !ponderhit {
+            // The engine's clock is already running, so stopping counts as thinking time
+            match current_engine.stop_pondering(bestmove_timeout) {
+                EngineResult::Ok(()) => {}
+                EngineResult::Err(err) => return Err(err),
+                EngineResult::Timeout => {
+                    // The engine is still searching, so its bestmove would leak into the next game
+                    match_result.outcome = GameOutcome::LossByClock(stm);
+                    current_engine.restart()?;
+                    continue;
+                }
+                EngineResult::Disconnected => {
+                    match_result.outcome = GameOutcome::LossByDisconnection(stm);
+                    current_engine.restart()?;
+                    continue;
+                }
+            }
+        }
+
+        let bestmove_timeout =
+            bestmove_timeout.map(|timeout| timeout.saturating_sub(now.elapsed()));
+        if ponderhit {
+            // The engine is already searching this position; its clock starts now
+            current_engine.ponderhit()?;
+        } else {
+            current_engine.position(&game)?;
+
+            current_engine.write_line(&format!(
+                "go {}",
+                tc::to_usi_string(stm, &engine_time[0], &engine_time[1])
+            ))?;
+            current_engine.flush()?;
+        }
 
         match current_engine.wait_for_bestmove(stm, bestmove_timeout) {
             EngineResult::Err(err) => return Err(err),
@@ -270,6 +318,7 @@ fn run_match(
                 move_record.time_left = engine_time[stm.to_index()].remaining();
 
                 let m = move_record.m;
+                let ponder = move_record.ponder;
                 match_result.moves.push(move_record);
                 match_result.outcome = game.do_move(m);
 
@@ -277,7 +326,15 @@ fn run_match(
                     match_result.outcome = GameOutcome::LossByClock(stm);
                 }
 
-                do_adjudication(stm, adjudication, &mut match_result);
+                do_adjudication(stm, game.position(), adjudication, &mut match_result);
+
+                if options.ponder
+                    && !match_result.outcome.is_determined()
+                    && let Some(ponder) = ponder
+                {
+                    pondering[stm.to_index()] =
+                        start_pondering(current_engine, &game, ponder, &engine_time)?;
+                }
             }
 
             EngineResult::Timeout => {
@@ -289,11 +346,44 @@ fn run_match(
                 current_engine.restart()?;
             }
         };
+    }
 
-        if match_result.outcome.is_determined() {
-            return Ok(match_result);
+    // Leave no search running into the next game
+    for (i, predicted) in pondering.iter().enumerate() {
+        let engine = &mut engines[ticket.engines[i]];
+        if predicted.is_some()
+            && !matches!(
+                engine.stop_pondering(Some(5 * Duration::SECOND)),
+                EngineResult::Ok(())
+            )
+        {
+            engine.restart()?;
         }
     }
+
+    Ok(match_result)
+}
+
+// Sends `go ponder` on the position after `predicted`, the engine's guess at its opponent's reply
+// to the last move of `game`. Returns the move pondered on, or None if the guess is illegal or
+// ends the game, in which case there is nothing to ponder.
+fn start_pondering(
+    engine: &mut engine::Engine,
+    game: &shogi::Game,
+    predicted: shogi::Move,
+    engine_time: &[tc::EngineTime; 2],
+) -> Result<Option<shogi::Move>, std::io::Error> {
+    let mut ponder_game = game.clone();
+    if ponder_game.do_move(predicted).is_determined() {
+        return Ok(None);
+    }
+    engine.position(&ponder_game)?;
+    engine.write_line(&format!(
+        "go ponder {}",
+        tc::to_usi_string(ponder_game.stm(), &engine_time[0], &engine_time[1])
+    ))?;
+    engine.flush()?;
+    Ok(Some(predicted))
 }
 
 #[cfg(test)]
@@ -307,6 +397,7 @@ mod tests {
                 id: 0,
                 engines: [0, 1],
                 opening: shogi::Position::default(),
+                opening_source: 0,
             },
             game_start: Utc::now(),
             outcome: GameOutcome::Undetermined,
@@ -339,9 +430,11 @@ mod tests {
         mr.outcome = GameOutcome::Undetermined;
         do_adjudication(
             Color::Gote,
+            &shogi::Position::default(),
             &cli::AdjudicationOptions {
                 max_moves: None,
                 draw: None,
+                mate: None,
                 resign: Some(cli::ResignAdjudicationOptions {
                     two_sided: false,
                     move_count: 1,
@@ -355,9 +448,11 @@ mod tests {
         mr.outcome = GameOutcome::Undetermined;
         do_adjudication(
             Color::Gote,
+            &shogi::Position::default(),
             &cli::AdjudicationOptions {
                 max_moves: None,
                 draw: None,
+                mate: None,
                 resign: Some(cli::ResignAdjudicationOptions {
                     two_sided: false,
                     move_count: 2,
@@ -371,9 +466,11 @@ mod tests {
         mr.outcome = GameOutcome::Undetermined;
         do_adjudication(
             Color::Gote,
+            &shogi::Position::default(),
             &cli::AdjudicationOptions {
                 max_moves: None,
                 draw: None,
+                mate: None,
                 resign: Some(cli::ResignAdjudicationOptions {
                     two_sided: false,
                     move_count: 3,
@@ -387,9 +484,11 @@ mod tests {
         mr.outcome = GameOutcome::Undetermined;
         do_adjudication(
             Color::Gote,
+            &shogi::Position::default(),
             &cli::AdjudicationOptions {
                 max_moves: None,
                 draw: None,
+                mate: None,
                 resign: Some(cli::ResignAdjudicationOptions {
                     two_sided: true,
                     move_count: 2,
@@ -403,9 +502,11 @@ mod tests {
         mr.outcome = GameOutcome::Undetermined;
         do_adjudication(
             Color::Gote,
+            &shogi::Position::default(),
             &cli::AdjudicationOptions {
                 max_moves: None,
                 draw: None,
+                mate: None,
                 resign: Some(cli::ResignAdjudicationOptions {
                     two_sided: true,
                     move_count: 4,
@@ -419,9 +520,11 @@ mod tests {
         mr.outcome = GameOutcome::Undetermined;
         do_adjudication(
             Color::Gote,
+            &shogi::Position::default(),
             &cli::AdjudicationOptions {
                 max_moves: None,
                 draw: None,
+                mate: None,
                 resign: Some(cli::ResignAdjudicationOptions {
                     two_sided: true,
                     move_count: 6,
@@ -450,9 +553,11 @@ mod tests {
         mr.outcome = GameOutcome::Undetermined;
         do_adjudication(
             Color::Gote,
+            &shogi::Position::default(),
             &cli::AdjudicationOptions {
                 max_moves: None,
                 draw: None,
+                mate: None,
                 resign: Some(cli::ResignAdjudicationOptions {
                     two_sided: false,
                     move_count: 2,
@@ -466,9 +571,11 @@ mod tests {
         mr.outcome = GameOutcome::Undetermined;
         do_adjudication(
             Color::Gote,
+            &shogi::Position::default(),
             &cli::AdjudicationOptions {
                 max_moves: None,
                 draw: None,
+                mate: None,
                 resign: Some(cli::ResignAdjudicationOptions {
                     two_sided: true,
                     move_count: 2,
@@ -482,9 +589,11 @@ mod tests {
         mr.outcome = GameOutcome::Undetermined;
         do_adjudication(
             Color::Gote,
+            &shogi::Position::default(),
             &cli::AdjudicationOptions {
                 max_moves: None,
                 draw: None,
+                mate: None,
                 resign: Some(cli::ResignAdjudicationOptions {
                     two_sided: true,
                     move_count: 4,
@@ -513,9 +622,11 @@ mod tests {
         mr.outcome = GameOutcome::Undetermined;
         do_adjudication(
             Color::Gote,
+            &shogi::Position::default(),
             &cli::AdjudicationOptions {
                 max_moves: None,
                 draw: None,
+                mate: None,
                 resign: Some(cli::ResignAdjudicationOptions {
                     two_sided: false,
                     move_count: 2,
@@ -544,9 +655,11 @@ mod tests {
         mr.outcome = GameOutcome::Undetermined;
         do_adjudication(
             Color::Gote,
+            &shogi::Position::default(),
             &cli::AdjudicationOptions {
                 max_moves: None,
                 draw: None,
+                mate: None,
                 resign: Some(cli::ResignAdjudicationOptions {
                     two_sided: false,
                     move_count: 2,
@@ -557,4 +670,166 @@ mod tests {
         );
         assert!(mr.outcome == GameOutcome::WinByAdjudication(Color::Sente));
     }
+
+    #[test]
+    fn test_mate_adjudication() {
+        let adjudication = cli::AdjudicationOptions {
+            max_moves: None,
+            draw: None,
+            resign: None,
+            mate: Some(cli::MateAdjudicationOptions {
+                plies: 5,
+                nodes: 100000,
+            }),
+        };
+
+        // Gote mates with R*9g, 9g8g+ and G*8i against a bare king
+        let position = shogi::Position::parse("4k4/9/9/9/9/9/9/9/K8 w r2g 1").unwrap();
+        let mut mr = new_mr();
+        append(&mut mr, Color::Sente, Score::Cp(0));
+        do_adjudication(Color::Sente, &position, &adjudication, &mut mr);
+        assert_eq!(mr.outcome, GameOutcome::WinByForcedMate(Color::Gote));
+
+        // A single gold cannot mate
+        let position = shogi::Position::parse("4k4/9/9/9/9/9/9/9/K8 w g 1").unwrap();
+        let mut mr = new_mr();
+        append(&mut mr, Color::Sente, Score::Cp(0));
+        do_adjudication(Color::Sente, &position, &adjudication, &mut mr);
+        assert_eq!(mr.outcome, GameOutcome::Undetermined);
+    }
+
+    #[cfg(unix)]
+    #[test]
+    fn ponder_hit_and_miss() {
+        // Sente predicts 3c3d correctly, then predicts 8c8d and is answered with 4c4d
+        let sente = engine::tests::scripted_engine(
+            "ponder-sente",
+            r#"        "go 0") echo "bestmove 7g7f ponder 3c3d" ;;
+        "ponderhit 2") echo "bestmove 2g2f ponder 8c8d" ;;
+        "stop 4") sleep 0.3; echo "bestmove 6g6f" ;;
+        "go 4") echo "bestmove 6g6f" ;;"#,
+        );
+        let gote = engine::tests::scripted_engine(
+            "ponder-gote",
+            r#"        "go 1") sleep 0.3; echo "bestmove 3c3d" ;;
+        "go 3") echo "bestmove 4c4d" ;;"#,
+        );
+        let tc = tc::TimeControl::parse("10+0").unwrap();
+        let engine_options = [
+            cli::EngineOptions {
+                builder: sente.clone(),
+                time_control: tc,
+                ponder: true,
+                ..cli::EngineOptions::default()
+            },
+            cli::EngineOptions {
+                builder: gote.clone(),
+                time_control: tc,
+                ..cli::EngineOptions::default()
+            },
+        ];
+        let mut engines = [sente.init().unwrap(), gote.init().unwrap()];
+        let adjudication = cli::AdjudicationOptions {
+            max_moves: Some(5),
+            ..cli::AdjudicationOptions::default()
+        };
+        let mr = run_match(
+            &engine_options,
+            &adjudication,
+            shogi::GameRules::default(),
+            &mut engines,
+            &new_mr().ticket,
+        )
+        .unwrap();
+        drop(engines);
+
+        assert_eq!(GameOutcome::DrawByMoveLimit, mr.outcome);
+        let moves: Vec<String> = mr.moves.iter().map(|m| m.mstr.clone()).collect();
+        assert_eq!(vec!["7g7f", "3c3d", "2g2f", "4c4d", "6g6f"], moves);
+        // Gote's thinking time is not charged to the pondering engine
+        assert!(mr.moves[2].measured_time < 200 * Duration::MILLISECOND);
+        // Waiting for the engine to stop after a miss is charged to its clock
+        assert!(mr.moves[4].measured_time >= 300 * Duration::MILLISECOND);
+
+        let log = std::fs::read_to_string(format!("{}.log", sente.cmd)).unwrap();
+        let commands: Vec<&str> = log
+            .lines()
+            .skip_while(|line| *line != "usinewgame")
+            .skip(1)
+            .map(|line| match line.split_once(" moves ") {
+                Some((_, moves)) => moves,
+                None if line.starts_with("go ponder") => "go ponder",
+                None => line.split(' ').next().unwrap(),
+            })
+            .collect();
+        assert_eq!(
+            vec![
+                "position",
+                "go",
+                "7g7f 3c3d",
+                "go ponder",
+                "ponderhit",
+                "7g7f 3c3d 2g2f 8c8d",
+                "go ponder",
+                "stop",
+                "7g7f 3c3d 2g2f 4c4d",
+                "go",
+                "quit"
+            ],
+            commands
+        );
+
+        for builder in [sente, gote] {
+            std::fs::remove_file(&builder.cmd).unwrap();
+            std::fs::remove_file(format!("{}.log", builder.cmd)).unwrap();
+        }
+    }
+
+    #[cfg(unix)]
+    #[test]
+    fn ponder_stop_timeout() {
+        // Sente predicts 3c3d, is answered with 8c8d and never answers the stop
+        let sente = engine::tests::scripted_engine(
+            "stop-timeout-sente",
+            r#"        "go 0") echo "bestmove 7g7f ponder 3c3d" ;;"#,
+        );
+        let gote = engine::tests::scripted_engine(
+            "stop-timeout-gote",
+            r#"        "go 1") echo "bestmove 8c8d" ;;"#,
+        );
+        let tc = tc::TimeControl::parse("0.5+0").unwrap();
+        let engine_options = [
+            cli::EngineOptions {
+                builder: sente.clone(),
+                time_control: tc,
+                ponder: true,
+                ..cli::EngineOptions::default()
+            },
+            cli::EngineOptions {
+                builder: gote.clone(),
+                time_control: tc,
+                ..cli::EngineOptions::default()
+            },
+        ];
+        let mut engines = [sente.init().unwrap(), gote.init().unwrap()];
+        let mr = run_match(
+            &engine_options,
+            &cli::AdjudicationOptions::default(),
+            shogi::GameRules::default(),
+            &mut engines,
+            &new_mr().ticket,
+        )
+        .unwrap();
+        drop(engines);
+
+        assert_eq!(GameOutcome::LossByClock(Color::Sente), mr.outcome);
+        // The engine still searching is restarted rather than carried into the next game
+        let log = std::fs::read_to_string(format!("{}.log", sente.cmd)).unwrap();
+        assert_eq!(2, log.lines().filter(|line| *line == "usi").count());
+
+        for builder in [sente, gote] {
+            std::fs::remove_file(&builder.cmd).unwrap();
+            std::fs::remove_file(format!("{}.log", builder.cmd)).unwrap();
+        }
+    }
 }
@@ -0,0 +1,283 @@
+use super::{Color, PieceType, Square};
+use lazy_static::lazy_static;
+use std::ops;
+
+const BOARD_MASK: u128 = (1 << 81) - 1;
+
+#[derive(Copy, Clone, PartialEq, Eq, Debug, Default)]
+#[repr(transparent)]
+pub struct Bitboard(u128);
+
+impl Bitboard {
+    pub const EMPTY: Bitboard = Bitboard(0);
+
+    pub fn from_square(sq: Square) -> Bitboard {
+        Bitboard(1 << sq.to_index())
+    }
+
+    pub fn contains(self, sq: Square) -> bool {
+        self.0 & (1 << sq.to_index()) != 0
+    }
+
+    pub fn is_empty(self) -> bool {
+        self.0 == 0
+    }
+
+    pub fn count(self) -> u32 {
+        self.0.count_ones()
+    }
+
+    pub fn lsb(self) -> Option<Square> {
+        if self.is_empty() {
+            None
+        } else {
+            Some(Square(self.0.trailing_zeros() as u8))
+        }
+    }
+
+    pub fn msb(self) -> Option<Square> {
+        if self.is_empty() {
+            None
+        } else {
+            Some(Square((127 - self.0.leading_zeros()) as u8))
+        }
+    }
+
+    pub fn file(file: i8) -> Bitboard {
+        FILES[file as usize]
+    }
+
+    pub fn rank(rank: i8) -> Bitboard {
+        RANKS[rank as usize]
+    }
+
+    /// Squares of a board with `size` files and ranks, placed in the corner holding 1a.
+    pub fn board(size: i8) -> Bitboard {
+        let rank_mask = (1u128 << size) - 1;
+        Bitboard((0..size).fold(0, |bb, rank| bb | rank_mask << (rank * 9)))
+    }
+}
+
+impl Iterator for Bitboard {
+    type Item = Square;
+
+    fn next(&mut self) -> Option<Square> {
+        let sq = self.lsb()?;
+        self.0 &= self.0 - 1;
+        Some(sq)
+    }
+}
+
+impl ops::BitAnd for Bitboard {
+    type Output = Self;
+
+    fn bitand(self, other: Self) -> Self::Output {
+        Bitboard(self.0 & other.0)
+    }
+}
+
+impl ops::BitOr for Bitboard {
+    type Output = Self;
+
+    fn bitor(self, other: Self) -> Self::Output {
+        Bitboard(self.0 | other.0)
+    }
+}
+
+impl ops::BitXor for Bitboard {
+    type Output = Self;
+
+    fn bitxor(self, other: Self) -> Self::Output {
+        Bitboard(self.0 ^ other.0)
+    }
+}
+
+impl ops::Not for Bitboard {
+    type Output = Self;
+
+    fn not(self) -> Self::Output {
+        Bitboard(!self.0 & BOARD_MASK)
+    }
+}
+
+impl ops::BitAndAssign for Bitboard {
+    fn bitand_assign(&mut self, other: Self) {
+        self.0 &= other.0;
+    }
+}
+
+impl ops::BitOrAssign for Bitboard {
+    fn bitor_assign(&mut self, other: Self) {
+        self.0 |= other.0;
+    }
+}
+
+impl ops::BitXorAssign for Bitboard {
+    fn bitxor_assign(&mut self, other: Self) {
+        self.0 ^= other.0;
+    }
+}
+
+// Directions are (file, rank) steps. Sente moves towards rank 0 (rank a).
+const DIRECTIONS: [(i8, i8); 8] = [
+    (0, -1),
+    (0, 1),
+    (-1, 0),
+    (1, 0),
+    (-1, -1),
+    (1, -1),
+    (-1, 1),
+    (1, 1),
+];
+const ORTHOGONALS: [usize; 4] = [0, 1, 2, 3];
+const DIAGONALS: [usize; 4] = [4, 5, 6, 7];
+
+fn direction_index(file: i8, rank: i8) -> usize {
+    DIRECTIONS.iter().position(|&d| d == (file, rank)).unwrap()
+}
+
+fn is_positive_direction(dir: usize) -> bool {
+    let (file, rank) = DIRECTIONS[dir];
+    rank * 9 + file > 0
+}
+
+fn forward_direction(color: Color) -> usize {
+    match color {
+        Color::Sente => 0,
+        Color::Gote => 1,
+    }
+}
+
+lazy_static! {
+    static ref FILES: [Bitboard; 9] = std::array::from_fn(|file| {
+        (0..9)
+            .filter_map(|rank| Square::new(file as i8, rank))
+            .fold(Bitboard::EMPTY, |bb, sq| bb | Bitboard::from_square(sq))
+    });
+    static ref RANKS: [Bitboard; 9] = std::array::from_fn(|rank| {
+        (0..9)
+            .filter_map(|file| Square::new(file, rank as i8))
+            .fold(Bitboard::EMPTY, |bb, sq| bb | Bitboard::from_square(sq))
+    });
+    static ref RAYS: [[Bitboard; 81]; 8] = {
+        let mut rays = [[Bitboard::EMPTY; 81]; 8];
+        for (dir, &(df, dr)) in DIRECTIONS.iter().enumerate() {
+            for (i, ray) in rays[dir].iter_mut().enumerate() {
+                let mut sq = Square(i as u8);
+                while let Some(next) = Square::new(sq.file() + df, sq.rank() + dr) {
+                    *ray |= Bitboard::from_square(next);
+                    sq = next;
+                }
+            }
+        }
+        rays
+    };
+    static ref STEP_ATTACKS: [[[Bitboard; 81]; 16]; 2] = {
+        let mut table = [[[Bitboard::EMPTY; 81]; 16]; 2];
+        for color in [Color::Sente, Color::Gote] {
+            for pt in [
+                PieceType::Pawn,
+                PieceType::Knight,
+                PieceType::Silver,
+                PieceType::Gold,
+                PieceType::King,
+            ] {
+                for (i, attacks) in table[color.to_index()][pt as usize].iter_mut().enumerate() {
+                    let from = Square(i as u8);
+                    *attacks = (0..81)
+                        .map(|j| Square(j as u8))
+                        .filter(|&to| (to - from).could_be_piece_move(color, pt))
+                        .fold(Bitboard::EMPTY, |bb, to| bb | Bitboard::from_square(to));
+                }
+            }
+        }
+        table
+    };
+    static ref BETWEEN: Vec<[Bitboard; 81]> = {
+        let mut table = vec![[Bitboard::EMPTY; 81]; 81];
+        for (a, row) in table.iter_mut().enumerate() {
+            for ray in RAYS.iter() {
+                for b in ray[a] {
+                    row[b.to_index()] = ray[a] & !ray[b.to_index()] & !Bitboard::from_square(b);
+                }
+            }
+        }
+        table
+    };
+    static ref LINE: Vec<[Bitboard; 81]> = {
+        let mut table = vec![[Bitboard::EMPTY; 81]; 81];
+        for (a, row) in table.iter_mut().enumerate() {
+            for (dir, &(df, dr)) in DIRECTIONS.iter().enumerate() {
+                let line = RAYS[dir][a] | RAYS[direction_index(-df, -dr)][a];
+                for b in RAYS[dir][a] {
+                    row[b.to_index()] = line | Bitboard::from_square(Square(a as u8));
+                }
+            }
+        }
+        table
+    };
+}
+
+fn ray_attacks(dir: usize, sq: Square, occupied: Bitboard) -> Bitboard {
+    let ray = RAYS[dir][sq.to_index()];
+    let blockers = ray & occupied;
+    let blocker = if is_positive_direction(dir) {
+        blockers.lsb()
+    } else {
+        blockers.msb()
+    };
+    match blocker {
+        Some(blocker) => ray ^ RAYS[dir][blocker.to_index()],
+        None => ray,
+    }
+}
+
+fn step_attacks(color: Color, pt: PieceType, sq: Square) -> Bitboard {
+    STEP_ATTACKS[color.to_index()][pt as usize][sq.to_index()]
+}
+
+/// Squares strictly between `a` and `b` if they share a line, otherwise empty.
+pub fn between(a: Square, b: Square) -> Bitboard {
+    BETWEEN[a.to_index()][b.to_index()]
+}
+
+/// The full line through `a` and `b` if they share a line, otherwise empty.
+pub fn line(a: Square, b: Square) -> Bitboard {
+    LINE[a.to_index()][b.to_index()]
+}
+
+pub fn lance_attacks(color: Color, sq: Square, occupied: Bitboard) -> Bitboard {
+    ray_attacks(forward_direction(color), sq, occupied)
+}
+
+pub fn bishop_attacks(sq: Square, occupied: Bitboard) -> Bitboard {
+    DIAGONALS.iter().fold(Bitboard::EMPTY, |bb, &dir| {
+        bb | ray_attacks(dir, sq, occupied)
+    })
+}
+
+pub fn rook_attacks(sq: Square, occupied: Bitboard) -> Bitboard {
+    ORTHOGONALS.iter().fold(Bitboard::EMPTY, |bb, &dir| {
+        bb | ray_attacks(dir, sq, occupied)
+    })
+}
+
+/// Squares attacked by a piece of type `pt` and colour `color` standing on `sq`.
+pub fn attacks(color: Color, pt: PieceType, sq: Square, occupied: Bitboard) -> Bitboard {
+    match pt {
+        PieceType::None => Bitboard::EMPTY,
+        PieceType::Pawn | PieceType::Knight | PieceType::Silver | PieceType::King => {
+            step_attacks(color, pt, sq)
+        }
+        PieceType::Gold
+        | PieceType::Tokin
+        | PieceType::NariLance
+        | PieceType::NariKnight
+        | PieceType::NariSilver => step_attacks(color, PieceType::Gold, sq),
+        PieceType::Lance => lance_attacks(color, sq, occupied),
+        PieceType::Bishop => bishop_attacks(sq, occupied),
+        PieceType::Rook => rook_attacks(sq, occupied),
+        PieceType::Horse => bishop_attacks(sq, occupied) | step_attacks(color, PieceType::King, sq),
+        PieceType::Dragon => rook_attacks(sq, occupied) | step_attacks(color, PieceType::King, sq),
+    }
+}
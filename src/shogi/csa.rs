@@ -0,0 +1,556 @@
+use super::{
+    Color, GameOutcome, HAND_PTYPES, Hand, Move, PieceType, Place, Position, Square, Variant,
+};
+use std::fmt;
+use std::time::Duration;
+
+const PIECE_CODES: [(PieceType, &str); 14] = [
+    (PieceType::Pawn, "FU"),
+    (PieceType::Lance, "KY"),
+    (PieceType::Knight, "KE"),
+    (PieceType::Silver, "GI"),
+    (PieceType::Gold, "KI"),
+    (PieceType::Bishop, "KA"),
+    (PieceType::Rook, "HI"),
+    (PieceType::King, "OU"),
+    (PieceType::Tokin, "TO"),
+    (PieceType::NariLance, "NY"),
+    (PieceType::NariKnight, "NK"),
+    (PieceType::NariSilver, "NG"),
+    (PieceType::Horse, "UM"),
+    (PieceType::Dragon, "RY"),
+];
+
+// Number of pieces of each kind in a full set, used by "00AL".
+fn full_set_count(pt: PieceType) -> u8 {
+    match pt {
+        PieceType::Pawn => 18,
+        PieceType::Bishop | PieceType::Rook => 2,
+        _ => 4,
+    }
+}
+
+fn piece_code(pt: PieceType) -> &'static str {
+    PIECE_CODES
+        .iter()
+        .find(|(p, _)| *p == pt)
+        .map_or("* ", |(_, code)| code)
+}
+
+fn parse_piece_code(s: &str) -> Option<PieceType> {
+    PIECE_CODES
+        .iter()
+        .find(|(_, code)| *code == s)
+        .map(|(pt, _)| *pt)
+}
+
+fn color_sign(color: Color) -> char {
+    match color {
+        Color::Sente => '+',
+        Color::Gote => '-',
+    }
+}
+
+fn parse_color_sign(c: u8) -> Option<Color> {
+    match c {
+        b'+' => Some(Color::Sente),
+        b'-' => Some(Color::Gote),
+        _ => None,
+    }
+}
+
+// CSA squares are written as file digit then rank digit, e.g. "77" for 7g.
+fn parse_square(file: u8, rank: u8) -> Option<Square> {
+    if !(b'1'..=b'9').contains(&file) || !(b'1'..=b'9').contains(&rank) {
+        return None;
+    }
+    Square::new((file - b'1') as i8, (rank - b'1') as i8)
+}
+
+fn square_str(sq: Square) -> String {
+    format!("{}{}", sq.file() + 1, sq.rank() + 1)
+}
+
+impl Position {
+    /// Parse a CSA move such as "+7776FU" or "-0055KA" in the context of this position.
+    pub fn parse_csa_move(&self, s: &str) -> Option<Move> {
+        let bytes = s.as_bytes();
+        if bytes.len() != 7 || !s.is_ascii() || parse_color_sign(bytes[0])? != self.stm {
+            return None;
+        }
+        let to = parse_square(bytes[3], bytes[4])?;
+        let pt = parse_piece_code(&s[5..7])?;
+        if &s[1..3] == "00" {
+            return Some(Move::Drop(pt, to));
+        }
+        let from = parse_square(bytes[1], bytes[2])?;
+        let Place(color, current) = self.board[from.to_index()];
+        if current == PieceType::None || color != self.stm {
+            return None;
+        }
+        let promo = if pt == current {
+            false
+        } else if current.promotable() && pt == current.promote() {
+            true
+        } else {
+            return None;
+        };
+        Some(Move::Normal { from, to, promo })
+    }
+
+    /// Format a move in CSA notation, e.g. "+7776FU". The piece code is that of the piece after
+    /// the move, so promotions are indicated by the promoted piece.
+    pub fn format_csa_move(&self, m: Move) -> Option<String> {
+        let sign = color_sign(self.stm);
+        match m {
+            Move::Drop(pt, to) => Some(format!("{sign}00{}{}", square_str(to), piece_code(pt))),
+            Move::Normal { from, to, promo } => {
+                let pt = self.board[from.to_index()].1;
+                if pt == PieceType::None {
+                    return None;
+                }
+                let pt = if promo { pt.promote() } else { pt };
+                Some(format!(
+                    "{sign}{}{}{}",
+                    square_str(from),
+                    square_str(to),
+                    piece_code(pt)
+                ))
+            }
+            Move::None | Move::Win | Move::Resign => None,
+        }
+    }
+
+    fn write_csa(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        if self.is_clone_of(&Position::default()) {
+            writeln!(f, "PI")?;
+        } else {
+            for rank in 0..9 {
+                write!(f, "P{}", rank + 1)?;
+                for file in (0..9).rev() {
+                    let Place(color, pt) = self.board[Square::new(file, rank).unwrap().to_index()];
+                    if pt == PieceType::None {
+                        write!(f, " * ")?;
+                    } else {
+                        write!(f, "{}{}", color_sign(color), piece_code(pt))?;
+                    }
+                }
+                writeln!(f)?;
+            }
+            for color in [Color::Sente, Color::Gote] {
+                let hand = &self.hand[color.to_index()];
+                if HAND_PTYPES.iter().all(|&pt| hand.get(pt) == 0) {
+                    continue;
+                }
+                write!(f, "P{}", color_sign(color))?;
+                for pt in HAND_PTYPES.iter().rev() {
+                    for _ in 0..hand.get(*pt) {
+                        write!(f, "00{}", piece_code(*pt))?;
+                    }
+                }
+                writeln!(f)?;
+            }
+        }
+        writeln!(f, "{}", color_sign(self.stm))
+    }
+}
+
+/// A game record in CSA standard format (version 2.2).
+#[derive(Debug, Clone)]
+pub struct CsaRecord {
+    pub names: [Option<String>; 2],
+    pub event: Option<String>,
+    pub site: Option<String>,
+    pub start_time: Option<String>,
+    pub start: Position,
+    pub moves: Vec<(Move, Option<Duration>)>,
+    pub outcome: GameOutcome,
+}
+
+impl CsaRecord {
+    pub fn new(start: Position) -> CsaRecord {
+        CsaRecord {
+            names: [None, None],
+            event: None,
+            site: None,
+            start_time: None,
+            start,
+            moves: vec![],
+            outcome: GameOutcome::Undetermined,
+        }
+    }
+
+    /// Every position of the game, starting with `start`. Fails, naming the move, if one of the
+    /// moves is illegal.
+    pub fn positions(&self) -> Result<Vec<Position>, String> {
+        let mut positions = vec![self.start];
+        for (i, (m, _)) in self.moves.iter().enumerate() {
+            let Some(next) = positions.last().unwrap().do_move(*m) else {
+                return Err(format!("Move {} ({m}) is illegal", i + 1));
+            };
+            positions.push(next);
+        }
+        Ok(positions)
+    }
+
+    pub fn final_position(&self) -> Result<Position, String> {
+        Ok(*self.positions()?.last().unwrap())
+    }
+
+    /// Parse one or more records. Multiple records are separated by a line containing only "/".
+    pub fn parse_all(text: &str) -> Result<Vec<CsaRecord>, String> {
+        let mut records = vec![];
+        let mut lines = vec![];
+        for (i, line) in text.lines().enumerate() {
+            let line = line.trim_end();
+            if line == "/" {
+                records.push(CsaRecord::parse_record(&lines, records.len())?);
+                lines.clear();
+            } else {
+                // Several statements may share a line, separated by commas
+                lines.extend(line.split(',').map(|s| (i + 1, s)));
+            }
+        }
+        if lines.iter().any(|(_, s)| !s.trim().is_empty()) {
+            records.push(CsaRecord::parse_record(&lines, records.len())?);
+        }
+        Ok(records)
+    }
+
+    // Parses the record with zero-based `index` in its file, naming the record in errors.
+    fn parse_record(lines: &[(usize, &str)], index: usize) -> Result<CsaRecord, String> {
+        CsaRecord::parse_lines(lines).map_err(|err| format!("record {}, {err}", index + 1))
+    }
+
+    fn parse_lines(lines: &[(usize, &str)]) -> Result<CsaRecord, String> {
+        let mut record = CsaRecord::new(Position::default());
+        let mut board: Option<[Place; 81]> = None;
+        let mut hand = [Hand::default(); 2];
+        let mut position: Option<Position> = None;
+
+        for &(line_number, line) in lines {
+            let err = |what: &str| Err(format!("line {line_number}: {what}: {line}"));
+            let bytes = line.as_bytes();
+
+            if line.is_empty() || line.starts_with('\'') || line.starts_with('V') {
+                continue;
+            }
+
+            if let Some(position) = position.as_mut() {
+                match bytes[0] {
+                    b'+' | b'-' => {
+                        let Some(m) = position.parse_csa_move(line) else {
+                            return err("invalid move");
+                        };
+                        let Some(next) = position.do_move(m) else {
+                            return err("illegal move");
+                        };
+                        record.moves.push((m, None));
+                        *position = next;
+                    }
+                    b'T' => {
+                        let Ok(seconds) = line[1..].parse::<f64>() else {
+                            return err("invalid time");
+                        };
+                        if let Some(last) = record.moves.last_mut() {
+                            last.1 = Some(Duration::from_secs_f64(seconds));
+                        }
+                    }
+                    b'%' => {
+                        let stm = position.stm;
+                        record.outcome = match line {
+                            "%TORYO" => GameOutcome::Resignation(stm),
+                            "%TSUMI" => GameOutcome::Checkmated(stm),
+                            "%KACHI" => GameOutcome::WinInImpasse(stm),
+                            "%TIME_UP" => GameOutcome::LossByClock(stm),
+                            "%ILLEGAL_MOVE" => GameOutcome::LossByIllegal(stm),
+                            "%+ILLEGAL_ACTION" => GameOutcome::LossByIllegal(Color::Sente),
+                            "%-ILLEGAL_ACTION" => GameOutcome::LossByIllegal(Color::Gote),
+                            "%SENNICHITE" => GameOutcome::DrawBySennichite,
+                            "%JISHOGI" => GameOutcome::DrawByImpasse,
+                            "%MAX_MOVES" => GameOutcome::DrawByMoveLimit,
+                            "%HIKIWAKE" => GameOutcome::DrawByAdjudication,
+                            _ => GameOutcome::Undetermined,
+                        };
+                    }
+                    _ => return err("unexpected statement"),
+                }
+                continue;
+            }
+
+            match bytes[0] {
+                b'N' if line.len() >= 2 => match parse_color_sign(bytes[1]) {
+                    Some(color) => record.names[color.to_index()] = Some(line[2..].to_string()),
+                    None => return err("invalid name"),
+                },
+                b'$' => {
+                    if let Some((key, value)) = line.split_once(':') {
+                        match key {
+                            "$EVENT" => record.event = Some(value.to_string()),
+                            "$SITE" => record.site = Some(value.to_string()),
+                            "$START_TIME" => record.start_time = Some(value.to_string()),
+                            _ => {}
+                        }
+                    }
+                }
+                b'P' if line.starts_with("PI") => {
+                    let mut b = Position::default().board;
+                    for chunk in bytes[2..].chunks(4) {
+                        let Some(sq) = chunk.get(0..2).and_then(|s| parse_square(s[0], s[1]))
+                        else {
+                            return err("invalid square");
+                        };
+                        if chunk.len() != 4
+                            || parse_piece_code(str::from_utf8(&chunk[2..]).unwrap_or(""))
+                                != Some(b[sq.to_index()].1)
+                        {
+                            return err("invalid piece");
+                        }
+                        b[sq.to_index()] = Place::default();
+                    }
+                    board = Some(b);
+                }
+                b'P' if line.len() >= 2 && (b'1'..=b'9').contains(&bytes[1]) => {
+                    let rank = (bytes[1] - b'1') as i8;
+                    let b = board.get_or_insert([Place::default(); 81]);
+                    for i in 0..9 {
+                        let file = 8 - i as i8;
+                        let cell = line.get(2 + 3 * i..5 + 3 * i).unwrap_or(" * ").trim_end();
+                        let sq = Square::new(file, rank).unwrap();
+                        if cell.is_empty() || cell == " *" || cell == "*" {
+                            continue;
+                        }
+                        let (Some(color), Some(pt)) = (
+                            parse_color_sign(cell.as_bytes()[0]),
+                            cell.get(1..).and_then(parse_piece_code),
+                        ) else {
+                            return err("invalid piece");
+                        };
+                        b[sq.to_index()] = Place(color, pt);
+                    }
+                }
+                b'P' if line.len() >= 2 && parse_color_sign(bytes[1]).is_some() => {
+                    let color = parse_color_sign(bytes[1]).unwrap();
+                    let b = board.get_or_insert([Place::default(); 81]);
+                    for chunk in bytes[2..].chunks(4) {
+                        // Each piece is a square (or 00 for the hand) and a piece code
+                        if chunk.len() != 4 {
+                            return err("invalid piece");
+                        }
+                        let code = str::from_utf8(&chunk[2..4]).unwrap_or("");
+                        if &chunk[0..2] == b"00" && code == "AL" {
+                            for pt in HAND_PTYPES {
+                                let on_board = b.iter().filter(|p| p.1.demote() == pt).count();
+                                let in_hand = (hand[0].get(pt) + hand[1].get(pt)) as usize;
+                                let rest = (full_set_count(pt) as usize)
+                                    .saturating_sub(on_board + in_hand);
+                                *hand[color.to_index()].get_mut(pt) += rest as u8;
+                            }
+                            continue;
+                        }
+                        let Some(pt) = parse_piece_code(code) else {
+                            return err("invalid piece");
+                        };
+                        if &chunk[0..2] == b"00" {
+                            if !HAND_PTYPES.contains(&pt) {
+                                return err("invalid piece in hand");
+                            }
+                            *hand[color.to_index()].get_mut(pt) += 1;
+                        } else {
+                            let Some(sq) = parse_square(chunk[0], chunk[1]) else {
+                                return err("invalid square");
+                            };
+                            b[sq.to_index()] = Place(color, pt);
+                        }
+                    }
+                }
+                b'+' | b'-' if line.len() == 1 => {
+                    let Some(b) = board else {
+                        return err("side to move given before initial position");
+                    };
+                    let stm = parse_color_sign(bytes[0]).unwrap();
+                    let start = Position::new(Variant::Standard, b, hand, stm, 1);
+                    record.start = start;
+                    position = Some(start);
+                }
+                _ => return err("unexpected statement"),
+            }
+        }
+
+        if position.is_none() {
+            return Err(String::from("record has no initial position"));
+        }
+
+        Ok(record)
+    }
+}
+
+impl fmt::Display for CsaRecord {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        writeln!(f, "V2.2")?;
+        for color in [Color::Sente, Color::Gote] {
+            if let Some(name) = &self.names[color.to_index()] {
+                writeln!(f, "N{}{name}", color_sign(color))?;
+            }
+        }
+        if let Some(event) = &self.event {
+            writeln!(f, "$EVENT:{event}")?;
+        }
+        if let Some(site) = &self.site {
+            writeln!(f, "$SITE:{site}")?;
+        }
+        if let Some(start_time) = &self.start_time {
+            writeln!(f, "$START_TIME:{start_time}")?;
+        }
+        self.start.write_csa(f)?;
+
+        let mut position = self.start;
+        for (m, time) in &self.moves {
+            // An illegal final move is recorded by the ending instead
+            let (Some(text), Some(next)) = (position.format_csa_move(*m), position.do_move(*m))
+            else {
+                break;
+            };
+            writeln!(f, "{text}")?;
+            if let Some(time) = time {
+                writeln!(f, "T{}", time.as_secs())?;
+            }
+            position = next;
+        }
+
+        let ending = match self.outcome {
+            GameOutcome::Checkmated(_) => String::from("%TSUMI"),
+            GameOutcome::WinInImpasse(_) => String::from("%KACHI"),
+            GameOutcome::DrawBySennichite
+            | GameOutcome::DrawByPerpetual(_)
+            | GameOutcome::DrawByRepetitionAdjudication => String::from("%SENNICHITE"),
+            GameOutcome::Resignation(_) => String::from("%TORYO"),
+            GameOutcome::LossByClock(_) => String::from("%TIME_UP"),
+            GameOutcome::DrawByMoveLimit => String::from("%MAX_MOVES"),
+            GameOutcome::DrawByImpasse => String::from("%JISHOGI"),
+            GameOutcome::DrawByAdjudication => String::from("%HIKIWAKE"),
+            GameOutcome::LossByPerpetual(color)
+            | GameOutcome::LossBySennichite(color)
+            | GameOutcome::LossByIllegal(color) => {
+                format!("%{}ILLEGAL_ACTION", color_sign(color))
+            }
+            GameOutcome::Undetermined
+            | GameOutcome::LossByDisconnection(_)
+            | GameOutcome::WinByAdjudication(_)
+            | GameOutcome::WinByForcedMate(_) => String::from("%CHUDAN"),
+        };
+        writeln!(f, "{ending}")?;
+        writeln!(f, "'{}", self.outcome.to_string())
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn csa_move_round_trip() {
+        let position = Position::default();
+        let m = position.parse_csa_move("+7776FU").unwrap();
+        assert_eq!(Move::parse("7g7f"), Some(m));
+        assert_eq!(Some(String::from("+7776FU")), position.format_csa_move(m));
+        assert_eq!(None, position.parse_csa_move("-3334FU"));
+
+        let position = position.do_move(m).unwrap();
+        let position = position.do_move(Move::parse("3c3d").unwrap()).unwrap();
+        let m = position.parse_csa_move("+8822UM").unwrap();
+        assert_eq!(Move::parse("8h2b+"), Some(m));
+        assert_eq!(Some(String::from("+8822UM")), position.format_csa_move(m));
+
+        let position = position.do_move(m).unwrap();
+        let position = position.do_move(Move::parse("3a2b").unwrap()).unwrap();
+        let m = position.parse_csa_move("+0045KA").unwrap();
+        assert_eq!(Move::parse("B*4e"), Some(m));
+        assert_eq!(Some(String::from("+0045KA")), position.format_csa_move(m));
+    }
+
+    #[test]
+    fn csa_record_round_trip() {
+        let text = "\
+V2.2
+N+Sente
+N-Gote
+$EVENT:test
+PI
++
++7776FU,T3
+-3334FU
+T2
++8822UM
+-3122GI
+%TORYO
+/
+P1-KY-KE-GI-KI-OU-KI-GI-KE-KY
+P2 *  *  *  *  *  *  *  *  * 
+P3-FU-FU-FU-FU-FU-FU-FU-FU-FU
+P4 *  *  *  *  *  *  *  *  * 
+P5 *  *  *  *  *  *  *  *  * 
+P6 *  *  *  *  *  *  *  *  * 
+P7+FU+FU+FU+FU+FU+FU+FU+FU+FU
+P8 * +KA *  *  *  *  * +HI * 
+P9+KY+KE+GI+KI+OU+KI+GI+KE+KY
+P+00HI
+P-00AL
+-
+";
+        let records = CsaRecord::parse_all(text).unwrap();
+        assert_eq!(2, records.len());
+
+        let first = &records[0];
+        assert_eq!(Some(String::from("Sente")), first.names[0]);
+        assert_eq!(Some(String::from("test")), first.event);
+        assert_eq!(4, first.moves.len());
+        assert_eq!(Some(Duration::from_secs(3)), first.moves[0].1);
+        assert_eq!(Some(Duration::from_secs(2)), first.moves[1].1);
+        assert_eq!(GameOutcome::Resignation(Color::Sente), first.outcome);
+        assert_eq!(
+            Position::parse("lnsgkg1nl/1r5s1/pppppp1pp/6p2/9/2P6/PP1PPPPPP/7R1/LNSGKGSNL b Bb 5")
+                .unwrap(),
+            first.final_position().unwrap()
+        );
+
+        let second = &records[1];
+        assert_eq!(
+            Position::parse("lnsgkgsnl/9/ppppppppp/9/9/9/PPPPPPPPP/1B5R1/LNSGKGSNL w Rb 1")
+                .unwrap(),
+            second.start
+        );
+
+        for record in &records {
+            let reparsed = CsaRecord::parse_all(&record.to_string()).unwrap();
+            assert_eq!(record.start, reparsed[0].start);
+            assert_eq!(record.final_position(), reparsed[0].final_position());
+            assert_eq!(record.outcome, reparsed[0].outcome);
+        }
+
+        // A repetition loss is an illegal action of the loser, not a sennichite draw
+        let mut record = CsaRecord::new(Position::default());
+        record.outcome = GameOutcome::LossBySennichite(Color::Gote);
+        assert!(record.to_string().contains("\n%-ILLEGAL_ACTION\n"));
+    }
+
+    #[test]
+    fn csa_illegal_move_names_record() {
+        let text = "PI\n+\n+7776FU\n/\nPI\n+\n+7776FU\n-4131KI\n";
+        assert_eq!(
+            Err(String::from("record 2, line 8: illegal move: -4131KI")),
+            CsaRecord::parse_all(text).map(|records| records.len())
+        );
+    }
+
+    #[test]
+    fn csa_truncated_pieces() {
+        for text in ["P+0\n+\n", "P-00F\n+\n", "P+7776FU00\n+\n"] {
+            let line = text.lines().next().unwrap();
+            assert_eq!(
+                Err(format!("record 1, line 1: invalid piece: {line}")),
+                CsaRecord::parse_all(text).map(|records| records.len())
+            );
+        }
+    }
+}
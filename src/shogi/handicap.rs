@@ -0,0 +1,131 @@
+use super::Position;
+
+/// Handicap (駒落ち) starting positions. The handicap giver (上手) plays Gote and moves first.
+#[derive(Copy, Clone, PartialEq, Eq, Debug)]
+pub enum Handicap {
+    Lance,
+    RightLance,
+    Bishop,
+    Rook,
+    RookLance,
+    TwoPiece,
+    ThreePiece,
+    FourPiece,
+    FivePiece,
+    LeftFivePiece,
+    SixPiece,
+    EightPiece,
+    TenPiece,
+}
+
+impl Handicap {
+    pub const ALL: [Handicap; 13] = [
+        Handicap::Lance,
+        Handicap::RightLance,
+        Handicap::Bishop,
+        Handicap::Rook,
+        Handicap::RookLance,
+        Handicap::TwoPiece,
+        Handicap::ThreePiece,
+        Handicap::FourPiece,
+        Handicap::FivePiece,
+        Handicap::LeftFivePiece,
+        Handicap::SixPiece,
+        Handicap::EightPiece,
+        Handicap::TenPiece,
+    ];
+
+    pub fn parse(s: &str) -> Option<Handicap> {
+        Handicap::ALL.into_iter().find(|h| h.name() == s)
+    }
+
+    /// Short name used on the command line, e.g. "2mai".
+    pub fn name(self) -> &'static str {
+        match self {
+            Handicap::Lance => "kyo",
+            Handicap::RightLance => "rkyo",
+            Handicap::Bishop => "kaku",
+            Handicap::Rook => "hisha",
+            Handicap::RookLance => "hikyo",
+            Handicap::TwoPiece => "2mai",
+            Handicap::ThreePiece => "3mai",
+            Handicap::FourPiece => "4mai",
+            Handicap::FivePiece => "5mai",
+            Handicap::LeftFivePiece => "l5mai",
+            Handicap::SixPiece => "6mai",
+            Handicap::EightPiece => "8mai",
+            Handicap::TenPiece => "10mai",
+        }
+    }
+
+    /// Japanese name as used in the 手合割 field of KIF files, e.g. "二枚落ち".
+    pub fn to_japanese(self) -> &'static str {
+        match self {
+            Handicap::Lance => "香落ち",
+            Handicap::RightLance => "右香落ち",
+            Handicap::Bishop => "角落ち",
+            Handicap::Rook => "飛車落ち",
+            Handicap::RookLance => "飛香落ち",
+            Handicap::TwoPiece => "二枚落ち",
+            Handicap::ThreePiece => "三枚落ち",
+            Handicap::FourPiece => "四枚落ち",
+            Handicap::FivePiece => "五枚落ち",
+            Handicap::LeftFivePiece => "左五枚落ち",
+            Handicap::SixPiece => "六枚落ち",
+            Handicap::EightPiece => "八枚落ち",
+            Handicap::TenPiece => "十枚落ち",
+        }
+    }
+
+    fn sfen(self) -> &'static str {
+        match self {
+            Handicap::Lance => "lnsgkgsn1/1r5b1/ppppppppp/9/9/9/PPPPPPPPP/1B5R1/LNSGKGSNL w - 1",
+            Handicap::RightLance => {
+                "1nsgkgsnl/1r5b1/ppppppppp/9/9/9/PPPPPPPPP/1B5R1/LNSGKGSNL w - 1"
+            }
+            Handicap::Bishop => "lnsgkgsnl/1r7/ppppppppp/9/9/9/PPPPPPPPP/1B5R1/LNSGKGSNL w - 1",
+            Handicap::Rook => "lnsgkgsnl/7b1/ppppppppp/9/9/9/PPPPPPPPP/1B5R1/LNSGKGSNL w - 1",
+            Handicap::RookLance => "lnsgkgsn1/7b1/ppppppppp/9/9/9/PPPPPPPPP/1B5R1/LNSGKGSNL w - 1",
+            Handicap::TwoPiece => "lnsgkgsnl/9/ppppppppp/9/9/9/PPPPPPPPP/1B5R1/LNSGKGSNL w - 1",
+            Handicap::ThreePiece => "lnsgkgsn1/9/ppppppppp/9/9/9/PPPPPPPPP/1B5R1/LNSGKGSNL w - 1",
+            Handicap::FourPiece => "1nsgkgsn1/9/ppppppppp/9/9/9/PPPPPPPPP/1B5R1/LNSGKGSNL w - 1",
+            Handicap::FivePiece => "2sgkgsn1/9/ppppppppp/9/9/9/PPPPPPPPP/1B5R1/LNSGKGSNL w - 1",
+            Handicap::LeftFivePiece => "1nsgkgs2/9/ppppppppp/9/9/9/PPPPPPPPP/1B5R1/LNSGKGSNL w - 1",
+            Handicap::SixPiece => "2sgkgs2/9/ppppppppp/9/9/9/PPPPPPPPP/1B5R1/LNSGKGSNL w - 1",
+            Handicap::EightPiece => "3gkg3/9/ppppppppp/9/9/9/PPPPPPPPP/1B5R1/LNSGKGSNL w - 1",
+            Handicap::TenPiece => "4k4/9/ppppppppp/9/9/9/PPPPPPPPP/1B5R1/LNSGKGSNL w - 1",
+        }
+    }
+
+    /// The handicap whose starting position is `position`, if any.
+    pub fn detect(position: &Position) -> Option<Handicap> {
+        Handicap::ALL
+            .into_iter()
+            .find(|&h| position.is_clone_of(&Position::handicap(h)))
+    }
+}
+
+impl Position {
+    /// Starting position of a handicap game, with Gote to move.
+    pub fn handicap(handicap: Handicap) -> Position {
+        Position::parse(handicap.sfen()).unwrap()
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::shogi::Color;
+
+    #[test]
+    fn handicap_positions() {
+        for handicap in Handicap::ALL {
+            assert_eq!(Some(handicap), Handicap::parse(handicap.name()));
+            let position = Position::handicap(handicap);
+            assert_eq!(Color::Gote, position.stm());
+            assert!(position.has_legal_move());
+            assert_eq!(Some(handicap), Handicap::detect(&position));
+        }
+        assert_eq!(None, Handicap::detect(&Position::default()));
+    }
+}
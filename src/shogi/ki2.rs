@@ -0,0 +1,189 @@
+use super::{Color, Move, PieceType, Position, Square};
+
+// Words describing how a piece moves relative to its owner: forwards, backwards or sideways.
+fn vertical_word(color: Color, from: Square, to: Square) -> &'static str {
+    let from = from.normalize_to_sente(color);
+    let to = to.normalize_to_sente(color);
+    match to.rank().cmp(&from.rank()) {
+        std::cmp::Ordering::Less => "上",
+        std::cmp::Ordering::Greater => "引",
+        std::cmp::Ordering::Equal => "寄",
+    }
+}
+
+// How far to the right of its owner a square is. Sente's right is the 1-file, Gote's the 9-file.
+fn rightness(color: Color, sq: Square) -> i8 {
+    match color {
+        Color::Sente => -sq.file(),
+        Color::Gote => sq.file(),
+    }
+}
+
+impl Position {
+    // Relative position words (右/左/直/上/引/寄) that tell the moving piece apart from `rivals`.
+    fn ki2_disambiguation(
+        pt: PieceType,
+        color: Color,
+        from: Square,
+        to: Square,
+        rivals: &[Square],
+    ) -> String {
+        if rivals.is_empty() {
+            return String::new();
+        }
+
+        let vertical = vertical_word(color, from, to);
+        let group: Vec<Square> = rivals
+            .iter()
+            .copied()
+            .filter(|&sq| vertical_word(color, sq, to) == vertical)
+            .collect();
+        if group.is_empty() {
+            return String::from(vertical);
+        }
+
+        let ranged = matches!(pt, PieceType::Horse | PieceType::Dragon);
+        if !ranged && vertical == "上" && from.file() == to.file() {
+            return String::from("直");
+        }
+
+        let horizontal = |others: &[Square]| {
+            if others
+                .iter()
+                .all(|&sq| rightness(color, from) > rightness(color, sq))
+            {
+                Some("右")
+            } else if others
+                .iter()
+                .all(|&sq| rightness(color, from) < rightness(color, sq))
+            {
+                Some("左")
+            } else {
+                None
+            }
+        };
+        match (horizontal(rivals), horizontal(&group)) {
+            (Some(word), _) => String::from(word),
+            (None, Some(word)) => format!("{word}{vertical}"),
+            (None, None) => String::from(vertical),
+        }
+    }
+
+    /// KI2 move text, e.g. "▲７六歩", "△同　銀", "▲５八金右" or "▲２二角成". `last_to` is the
+    /// destination of the previous move, used to mark recaptures with 同.
+    pub fn format_ki2_move(&self, m: Move, last_to: Option<Square>) -> String {
+        let mark = match self.stm {
+            Color::Sente => "▲",
+            Color::Gote => "△",
+        };
+        let destination = |to: Square, pt: PieceType| {
+            if last_to != Some(to) {
+                to.to_japanese()
+            } else if pt.to_kanji().chars().count() == 1 {
+                String::from("同　")
+            } else {
+                String::from("同")
+            }
+        };
+
+        match m {
+            Move::Drop(pt, to) => {
+                // 打 is only needed when a piece on the board could also move there
+                let drop = if self.other_movers(pt, None, to).is_empty() {
+                    ""
+                } else {
+                    "打"
+                };
+                format!("{mark}{}{}{drop}", destination(to, pt), pt.to_kanji())
+            }
+            Move::Normal { from, to, promo } => {
+                let Some((color, pt)) = self.piece_at(from) else {
+                    return String::new();
+                };
+                let rivals: Vec<Square> = self.other_movers(pt, Some(from), to).collect();
+                let relative = Position::ki2_disambiguation(pt, color, from, to, &rivals);
+                let promotion = if promo {
+                    "成"
+                } else if pt.promotable()
+                    && (self.is_promo_square(from, color) || self.is_promo_square(to, color))
+                {
+                    "不成"
+                } else {
+                    ""
+                };
+                format!(
+                    "{mark}{}{}{relative}{promotion}",
+                    destination(to, pt),
+                    pt.to_kanji()
+                )
+            }
+            Move::None | Move::Win | Move::Resign => String::new(),
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn ki2(sfen: &str, m: &str) -> String {
+        let position = Position::parse(sfen).unwrap();
+        position.format_ki2_move(Move::parse(m).unwrap(), None)
+    }
+
+    #[test]
+    fn ki2_move_text() {
+        let position = Position::default();
+        let m = Move::parse("7g7f").unwrap();
+        assert_eq!("▲７六歩", position.format_ki2_move(m, None));
+
+        let position = position.do_move(m).unwrap();
+        let position = position.do_move(Move::parse("3c3d").unwrap()).unwrap();
+        let m = Move::parse("8h2b+").unwrap();
+        assert_eq!("▲２二角成", position.format_ki2_move(m, None));
+
+        let position = position.do_move(m).unwrap();
+        let m = Move::parse("3a2b").unwrap();
+        assert_eq!(
+            "△同　銀",
+            position.format_ki2_move(m, Square::parse(b'2', b'b'))
+        );
+    }
+
+    #[test]
+    fn ki2_disambiguation() {
+        // Golds on 6九 and 4九 both moving up to 5八
+        let sfen = "4k4/9/9/9/9/9/9/9/3G1G2K b - 1";
+        assert_eq!("▲５八金左", ki2(sfen, "6i5h"));
+        assert_eq!("▲５八金右", ki2(sfen, "4i5h"));
+
+        // Straight up beats left/right
+        let sfen = "4k4/9/9/9/9/9/9/9/3GG3K b - 1";
+        assert_eq!("▲５八金直", ki2(sfen, "5i5h"));
+        assert_eq!("▲５八金左", ki2(sfen, "6i5h"));
+
+        // Movement words when they are enough
+        let sfen = "4k4/9/9/9/9/9/9/3G5/5G2K b - 1";
+        assert_eq!("▲５八金寄", ki2(sfen, "6h5h"));
+        assert_eq!("▲５八金上", ki2(sfen, "4i5h"));
+
+        // Gote's right is the 9-file
+        let sfen = "k2g1g3/9/9/9/9/9/9/9/4K4 w - 1";
+        assert_eq!("△５二金右", ki2(sfen, "6a5b"));
+        assert_eq!("△５二金左", ki2(sfen, "4a5b"));
+
+        // Dragons never use 直
+        let sfen = "4k4/9/9/9/9/5+R3/9/4+R4/8K b - 1";
+        assert_eq!("▲５五龍左", ki2(sfen, "5h5e"));
+        assert_eq!("▲５五龍右", ki2(sfen, "4f5e"));
+        assert_eq!("▲９八龍引", ki2("4k4/9/9/9/9/9/+R8/4+R4/8K b - 1", "9g9h"));
+
+        // 打 only when a board piece could also reach the square
+        let sfen = "4k4/9/9/9/9/9/9/9/3G4K b G 1";
+        assert_eq!("▲５八金打", ki2(sfen, "G*5h"));
+        assert_eq!("▲１一金", ki2(sfen, "G*1a"));
+
+        // 不成 when promotion was possible
+        assert_eq!("▲２三銀不成", ki2("4k4/9/9/7S1/9/9/9/9/8K b - 1", "2d2c"));
+    }
+}
@@ -0,0 +1,2113 @@
+use std::collections::HashMap;
+use std::fmt;
+use std::ops;
+
+mod bitboard;
+mod csa;
+mod handicap;
+mod ki2;
+mod movegen;
+mod tsume;
+mod validate;
+mod variant;
+mod western;
+mod zobrist;
+
+pub use bitboard::Bitboard;
+pub use csa::CsaRecord;
+pub use handicap::Handicap;
+pub use movegen::{MoveList, MoveListIter};
+pub use validate::Violation;
+pub use variant::Variant;
+
+#[derive(Copy, Clone, PartialEq, Eq, Debug)]
+pub enum Color {
+    Sente,
+    Gote,
+}
+
+impl Color {
+    pub fn to_index(self) -> usize {
+        match self {
+            Color::Sente => 0,
+            Color::Gote => 1,
+        }
+    }
+
+    pub fn parse(s: &str) -> Option<Color> {
+        match s {
+            "b" => Some(Color::Sente),
+            "w" => Some(Color::Gote),
+            _ => None,
+        }
+    }
+}
+
+impl fmt::Display for Color {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        write!(
+            f,
+            "{}",
+            match self {
+                Color::Sente => "b",
+                Color::Gote => "w",
+            }
+        )
+    }
+}
+
+impl ops::Not for Color {
+    type Output = Self;
+
+    fn not(self) -> Self::Output {
+        match self {
+            Color::Sente => Color::Gote,
+            Color::Gote => Color::Sente,
+        }
+    }
+}
+
+#[derive(Copy, Clone, PartialEq, Eq, Debug, Default)]
+#[repr(transparent)]
+pub struct Square(u8);
+
+impl Square {
+    pub fn new(file: i8, rank: i8) -> Option<Square> {
+        let value = rank * 9 + file;
+        if (0..=8).contains(&rank) && (0..=8).contains(&file) {
+            Some(Square(value as u8))
+        } else {
+            None
+        }
+    }
+
+    pub fn parse(c0: u8, c1: u8) -> Option<Square> {
+        if !(b'1'..=b'9').contains(&c0) || !(b'a'..=b'i').contains(&c1) {
+            return None;
+        }
+        let file = c0 - b'1';
+        let rank = c1 - b'a';
+        Self::new(file as i8, rank as i8)
+    }
+
+    /// The `i`th square in SFEN order on a board with `size` files and ranks.
+    pub fn from_fen_ordering(i: usize, size: i8) -> Option<Square> {
+        let size = size as usize;
+        let file = (size - 1 - i % size) as i8;
+        let rank = (i / size) as i8;
+        Square::new(file, rank)
+    }
+
+    pub fn file(self) -> i8 {
+        (self.0 % 9) as i8
+    }
+
+    pub fn rank(self) -> i8 {
+        (self.0 / 9) as i8
+    }
+
+    pub fn to_index(self) -> usize {
+        self.0 as usize
+    }
+
+    pub fn normalize_to_sente(self, current_color: Color) -> Square {
+        match current_color {
+            Color::Sente => self,
+            Color::Gote => Square::new(self.file(), 8 - self.rank()).unwrap(),
+        }
+    }
+
+    /// Japanese square notation, e.g. "７六".
+    pub fn to_japanese(self) -> String {
+        const FILES: [char; 9] = ['１', '２', '３', '４', '５', '６', '７', '８', '９'];
+        const RANKS: [char; 9] = ['一', '二', '三', '四', '五', '六', '七', '八', '九'];
+        format!(
+            "{}{}",
+            FILES[self.file() as usize],
+            RANKS[self.rank() as usize]
+        )
+    }
+}
+
+impl ops::Sub for Square {
+    type Output = Delta;
+
+    fn sub(self, other: Self) -> Self::Output {
+        Delta {
+            file: self.file() - other.file(),
+            rank: self.rank() - other.rank(),
+        }
+    }
+}
+
+impl ops::Add<Delta> for Square {
+    type Output = Option<Square>;
+
+    fn add(self, other: Delta) -> Self::Output {
+        Square::new(self.file() + other.file, self.rank() + other.rank)
+    }
+}
+
+impl fmt::Display for Square {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        write!(
+            f,
+            "{}{}",
+            (b'1' + (self.file() as u8)) as char,
+            (b'a' + (self.rank() as u8)) as char,
+        )
+    }
+}
+
+#[derive(Copy, Clone, PartialEq, Eq, Debug, Default)]
+pub struct Delta {
+    pub file: i8,
+    pub rank: i8,
+}
+
+impl Delta {
+    pub fn normalize_to_sente(self, piece_color: Color) -> Delta {
+        match piece_color {
+            Color::Sente => self,
+            Color::Gote => Delta {
+                file: self.file,
+                rank: -self.rank,
+            },
+        }
+    }
+
+    pub fn signum(self) -> Delta {
+        Delta {
+            file: self.file.signum(),
+            rank: self.rank.signum(),
+        }
+    }
+
+    pub fn is_ring(self) -> bool {
+        (self.file != 0 || self.rank != 0) && self.file.abs() <= 1 && self.rank.abs() <= 1
+    }
+
+    pub fn could_be_piece_move(self, color: Color, pt: PieceType) -> bool {
+        let d = self.normalize_to_sente(color);
+        if d.rank == 0 && d.file == 0 {
+            return false;
+        }
+        let is_ring = self.is_ring();
+        match pt {
+            PieceType::None => false,
+            PieceType::King => is_ring,
+            PieceType::Rook => d.rank == 0 || d.file == 0,
+            PieceType::Dragon => is_ring || d.rank == 0 || d.file == 0,
+            PieceType::Bishop => d.rank.abs() == d.file.abs(),
+            PieceType::Horse => is_ring || d.rank.abs() == d.file.abs(),
+            PieceType::Knight => d.rank == -2 && d.file.abs() == 1,
+            PieceType::Lance => d.file == 0 && d.rank < 0,
+            PieceType::Pawn => d.file == 0 && d.rank == -1,
+            PieceType::Silver => is_ring && (d.rank == -1 || d.rank.abs() == d.file.abs()),
+            PieceType::Gold
+            | PieceType::Tokin
+            | PieceType::NariLance
+            | PieceType::NariSilver
+            | PieceType::NariKnight => is_ring && (d.rank == -1 || d.rank == 0 || d.file == 0),
+        }
+    }
+}
+
+#[derive(Copy, Clone, PartialEq, Eq, Debug, Default)]
+#[repr(u8)]
+pub enum PieceType {
+    #[default]
+    None = 0o00,
+    Pawn = 0o01,
+    Bishop = 0o02,
+    Rook = 0o03,
+    Lance = 0o04,
+    Knight = 0o05,
+    Silver = 0o06,
+    Gold = 0o07,
+    King = 0o10,
+    Tokin = 0o11,
+    Horse = 0o12,
+    Dragon = 0o13,
+    NariLance = 0o14,
+    NariKnight = 0o15,
+    NariSilver = 0o16,
+}
+
+impl PieceType {
+    pub fn promotable(self) -> bool {
+        self as u8 >= Self::Pawn as u8 && self as u8 <= Self::Silver as u8
+    }
+
+    pub fn promoted(self) -> bool {
+        self as u8 >= Self::Tokin as u8
+    }
+
+    pub fn promote(self) -> PieceType {
+        if self == Self::None || self == Self::Gold {
+            self
+        } else {
+            // SAFETY: Unpromotables have been excluded above; 0o17 can never be generated here
+            unsafe { std::mem::transmute::<u8, PieceType>((self as u8) | 0o10) }
+        }
+    }
+
+    pub fn demote(self) -> PieceType {
+        if !self.promoted() {
+            self
+        } else {
+            // SAFETY: Unpromoteds have been excluded above; all values between 0o00 and 0o07 are valid
+            unsafe { std::mem::transmute::<u8, PieceType>((self as u8) & 0o07) }
+        }
+    }
+
+    pub fn to_str(self, color: Color) -> &'static str {
+        match (color, self) {
+            (Color::Sente, PieceType::None) => "",
+            (Color::Sente, PieceType::Pawn) => "P",
+            (Color::Sente, PieceType::Bishop) => "B",
+            (Color::Sente, PieceType::Rook) => "R",
+            (Color::Sente, PieceType::Lance) => "L",
+            (Color::Sente, PieceType::Knight) => "N",
+            (Color::Sente, PieceType::Silver) => "S",
+            (Color::Sente, PieceType::Gold) => "G",
+            (Color::Sente, PieceType::King) => "K",
+            (Color::Sente, PieceType::Tokin) => "+P",
+            (Color::Sente, PieceType::Horse) => "+B",
+            (Color::Sente, PieceType::Dragon) => "+R",
+            (Color::Sente, PieceType::NariLance) => "+L",
+            (Color::Sente, PieceType::NariKnight) => "+N",
+            (Color::Sente, PieceType::NariSilver) => "+S",
+            (Color::Gote, PieceType::None) => "",
+            (Color::Gote, PieceType::Pawn) => "p",
+            (Color::Gote, PieceType::Bishop) => "b",
+            (Color::Gote, PieceType::Rook) => "r",
+            (Color::Gote, PieceType::Lance) => "l",
+            (Color::Gote, PieceType::Knight) => "n",
+            (Color::Gote, PieceType::Silver) => "s",
+            (Color::Gote, PieceType::Gold) => "g",
+            (Color::Gote, PieceType::King) => "k",
+            (Color::Gote, PieceType::Tokin) => "+p",
+            (Color::Gote, PieceType::Horse) => "+b",
+            (Color::Gote, PieceType::Dragon) => "+r",
+            (Color::Gote, PieceType::NariLance) => "+l",
+            (Color::Gote, PieceType::NariKnight) => "+n",
+            (Color::Gote, PieceType::NariSilver) => "+s",
+        }
+    }
+}
+
+impl PieceType {
+    /// Japanese piece name as used in KIF/KI2 move text.
+    pub fn to_kanji(self) -> &'static str {
+        match self {
+            PieceType::None => "",
+            PieceType::Pawn => "歩",
+            PieceType::Lance => "香",
+            PieceType::Knight => "桂",
+            PieceType::Silver => "銀",
+            PieceType::Gold => "金",
+            PieceType::Bishop => "角",
+            PieceType::Rook => "飛",
+            PieceType::King => "玉",
+            PieceType::Tokin => "と",
+            PieceType::NariLance => "成香",
+            PieceType::NariKnight => "成桂",
+            PieceType::NariSilver => "成銀",
+            PieceType::Horse => "馬",
+            PieceType::Dragon => "龍",
+        }
+    }
+
+    /// Single character Japanese piece name as used in board diagrams.
+    pub fn to_kanji_char(self) -> char {
+        match self {
+            PieceType::None => '・',
+            PieceType::NariLance => '杏',
+            PieceType::NariKnight => '圭',
+            PieceType::NariSilver => '全',
+            _ => self.to_kanji().chars().next().unwrap(),
+        }
+    }
+}
+
+impl fmt::Display for PieceType {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        write!(f, "{}", self.to_str(Color::Sente))
+    }
+}
+
+#[derive(Copy, Clone, PartialEq, Eq, Debug, Default)]
+pub enum Move {
+    #[default]
+    None,
+    Win,
+    Resign,
+    Drop(PieceType, Square),
+    Normal {
+        from: Square,
+        to: Square,
+        promo: bool,
+    },
+}
+
+impl Move {
+    pub fn parse(s: &str) -> Option<Move> {
+        if s.eq_ignore_ascii_case("null") {
+            return Some(Move::None);
+        }
+        if s.eq_ignore_ascii_case("win") {
+            return Some(Move::Win);
+        }
+        if s.eq_ignore_ascii_case("resign") {
+            return Some(Move::Resign);
+        }
+        let bytes = s.as_bytes();
+        if bytes.len() < 4 || bytes.len() > 5 {
+            return None;
+        }
+        if bytes[1] == b'*' {
+            if bytes.len() != 4 {
+                return None;
+            }
+            let pt = match bytes[0] {
+                b'P' => PieceType::Pawn,
+                b'N' => PieceType::Knight,
+                b'L' => PieceType::Lance,
+                b'S' => PieceType::Silver,
+                b'G' => PieceType::Gold,
+                b'B' => PieceType::Bishop,
+                b'R' => PieceType::Rook,
+                _ => return None,
+            };
+            let to = Square::parse(bytes[2], bytes[3])?;
+            Some(Move::Drop(pt, to))
+        } else {
+            if s.len() == 5 && bytes[4] != b'+' {
+                return None;
+            }
+            let promo = s.len() == 5;
+            let from = Square::parse(bytes[0], bytes[1])?;
+            let to = Square::parse(bytes[2], bytes[3])?;
+            Some(Move::Normal { from, to, promo })
+        }
+    }
+}
+
+impl fmt::Display for Move {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        match self {
+            Move::None => write!(f, "null"),
+            Move::Win => write!(f, "win"),
+            Move::Resign => write!(f, "resign"),
+            Move::Normal { from, to, promo } => {
+                write!(f, "{from}{to}{}", if *promo { "+" } else { "" })
+            }
+            Move::Drop(pt, sq) => write!(f, "{pt}*{sq}"),
+        }
+    }
+}
+
+/// The piece types that can be held in hand, from pawn to rook.
+pub const HAND_PTYPES: [PieceType; 7] = [
+    PieceType::Pawn,
+    PieceType::Lance,
+    PieceType::Knight,
+    PieceType::Silver,
+    PieceType::Gold,
+    PieceType::Bishop,
+    PieceType::Rook,
+];
+
+#[derive(Copy, Clone, PartialEq, Eq, Debug)]
+pub struct Place(Color, PieceType);
+
+impl Place {
+    fn is_empty(self) -> bool {
+        self.1 == PieceType::None
+    }
+}
+
+impl Default for Place {
+    fn default() -> Self {
+        Place(Color::Sente, PieceType::None)
+    }
+}
+
+impl fmt::Display for Place {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        write!(f, "{}", self.1.to_str(self.0),)
+    }
+}
+
+#[derive(Copy, Clone, PartialEq, Eq, Debug, Default)]
+pub struct Hand {
+    rook: u8,
+    bishop: u8,
+    gold: u8,
+    silver: u8,
+    knight: u8,
+    lance: u8,
+    pawn: u8,
+}
+
+impl Hand {
+    pub fn get(&self, pt: PieceType) -> u8 {
+        match pt {
+            PieceType::Rook => self.rook,
+            PieceType::Bishop => self.bishop,
+            PieceType::Gold => self.gold,
+            PieceType::Silver => self.silver,
+            PieceType::Knight => self.knight,
+            PieceType::Lance => self.lance,
+            PieceType::Pawn => self.pawn,
+            _ => panic!(),
+        }
+    }
+
+    pub fn get_mut(&mut self, pt: PieceType) -> &mut u8 {
+        match pt {
+            PieceType::Rook => &mut self.rook,
+            PieceType::Bishop => &mut self.bishop,
+            PieceType::Gold => &mut self.gold,
+            PieceType::Silver => &mut self.silver,
+            PieceType::Knight => &mut self.knight,
+            PieceType::Lance => &mut self.lance,
+            PieceType::Pawn => &mut self.pawn,
+            _ => panic!(),
+        }
+    }
+
+    fn set_from_parse(&mut self, pt: PieceType, modifier: Option<usize>) {
+        let count = modifier.unwrap_or(1) as u8;
+        match pt {
+            PieceType::Rook => self.rook = count,
+            PieceType::Bishop => self.bishop = count,
+            PieceType::Gold => self.gold = count,
+            PieceType::Silver => self.silver = count,
+            PieceType::Knight => self.knight = count,
+            PieceType::Lance => self.lance = count,
+            PieceType::Pawn => self.pawn = count,
+            _ => panic!(),
+        }
+    }
+}
+
+#[derive(Copy, Clone, PartialEq, Eq, Debug)]
+pub enum CheckState {
+    None,
+    Check,
+    Checkmate,
+}
+
+#[derive(Copy, Clone, PartialEq, Eq, Debug)]
+pub struct Position {
+    variant: Variant,
+    board: [Place; 81],
+    hand: [Hand; 2],
+    stm: Color,
+    ply: usize,
+    color_bb: [Bitboard; 2],
+    piece_bb: [Bitboard; 16],
+    key: u64,
+}
+
+impl Default for Position {
+    fn default() -> Self {
+        Position::parse("lnsgkgsnl/1r5b1/ppppppppp/9/9/9/PPPPPPPPP/1B5R1/LNSGKGSNL b - 1").unwrap()
+    }
+}
+
+impl Position {
+    pub fn is_clone_of(&self, other: &Position) -> bool {
+        self.board == other.board && self.hand == other.hand && self.stm == other.stm
+    }
+
+    fn new(
+        variant: Variant,
+        board: [Place; 81],
+        hand: [Hand; 2],
+        stm: Color,
+        ply: usize,
+    ) -> Position {
+        let mut position = Position {
+            variant,
+            board: [Place::default(); 81],
+            hand,
+            stm,
+            ply,
+            color_bb: [Bitboard::EMPTY; 2],
+            piece_bb: [Bitboard::EMPTY; 16],
+            key: 0,
+        };
+        for (i, &place) in board.iter().enumerate() {
+            position.set_place(Square(i as u8), place);
+        }
+        for color in [Color::Sente, Color::Gote] {
+            for pt in HAND_PTYPES {
+                position.key ^= zobrist::hand_key(color, pt, hand[color.to_index()].get(pt));
+            }
+        }
+        if stm == Color::Gote {
+            position.key ^= zobrist::side_key();
+        }
+        position
+    }
+
+    /// The position reflected left to right, so that file 1 becomes file 9 (or the last file of
+    /// a smaller board). Hands and the side to move are unchanged.
+    pub fn mirror(&self) -> Position {
+        let size = self.variant.size();
+        let mut board = [Place::default(); 81];
+        for (i, &place) in self.board.iter().enumerate() {
+            let sq = Square(i as u8);
+            if self.is_on_board(sq) {
+                let mirrored = Square::new(size - 1 - sq.file(), sq.rank()).unwrap();
+                board[mirrored.to_index()] = place;
+            }
+        }
+        Position::new(self.variant, board, self.hand, self.stm, self.ply)
+    }
+
+    /// The same position seen from the other side: the board is rotated by 180 degrees, every
+    /// piece and hand changes colour, and the other side is to move.
+    pub fn flip_colors(&self) -> Position {
+        let size = self.variant.size();
+        let mut board = [Place::default(); 81];
+        for (i, &place) in self.board.iter().enumerate() {
+            let sq = Square(i as u8);
+            if self.is_on_board(sq) && !place.is_empty() {
+                let rotated = Square::new(size - 1 - sq.file(), size - 1 - sq.rank()).unwrap();
+                board[rotated.to_index()] = Place(!place.0, place.1);
+            }
+        }
+        let hand = [self.hand[1], self.hand[0]];
+        Position::new(self.variant, board, hand, !self.stm, self.ply)
+    }
+
+    /// Zobrist hash of the board, both hands and the side to move. The ply count is not hashed.
+    pub fn key(&self) -> u64 {
+        self.key
+    }
+
+    fn set_place(&mut self, sq: Square, place: Place) {
+        let bb = Bitboard::from_square(sq);
+        let old = self.board[sq.to_index()];
+        if !old.is_empty() {
+            self.color_bb[old.0.to_index()] ^= bb;
+            self.piece_bb[old.1 as usize] ^= bb;
+        }
+        if !place.is_empty() {
+            self.color_bb[place.0.to_index()] ^= bb;
+            self.piece_bb[place.1 as usize] ^= bb;
+        }
+        self.key ^= zobrist::place_key(sq, old) ^ zobrist::place_key(sq, place);
+        self.board[sq.to_index()] = place;
+    }
+
+    fn set_hand_count(&mut self, color: Color, pt: PieceType, count: u8) {
+        let in_hand = self.hand[color.to_index()].get_mut(pt);
+        self.key ^= zobrist::hand_key(color, pt, *in_hand) ^ zobrist::hand_key(color, pt, count);
+        *in_hand = count;
+    }
+
+    pub fn variant(&self) -> Variant {
+        self.variant
+    }
+
+    pub fn stm(&self) -> Color {
+        self.stm
+    }
+
+    pub fn ply(&self) -> usize {
+        self.ply
+    }
+
+    pub fn hand(&self, color: Color) -> &Hand {
+        &self.hand[color.to_index()]
+    }
+
+    pub fn piece_at(&self, sq: Square) -> Option<(Color, PieceType)> {
+        let place = self.board[sq.to_index()];
+        if place.is_empty() {
+            None
+        } else {
+            Some((place.0, place.1))
+        }
+    }
+
+    pub fn king_sq(&self, king_color: Color) -> Square {
+        self.king_square(king_color).unwrap()
+    }
+
+    pub fn is_empty(&self, sq: Square) -> bool {
+        self.board[sq.to_index()].1 == PieceType::None
+    }
+
+    pub fn is_enemy(&self, sq: Square) -> bool {
+        let place = self.board[sq.to_index()];
+        place.0 != self.stm && place.1 != PieceType::None
+    }
+
+    pub fn is_friendly(&self, sq: Square) -> bool {
+        let place = self.board[sq.to_index()];
+        place.0 == self.stm && place.1 != PieceType::None
+    }
+
+    pub fn is_in_check(&self) -> bool {
+        !self.checkers().is_empty()
+    }
+
+    pub fn get_check_state(&self) -> CheckState {
+        if !self.is_in_check() {
+            CheckState::None
+        } else if self.has_legal_move() {
+            CheckState::Check
+        } else {
+            CheckState::Checkmate
+        }
+    }
+
+    // 二歩
+    fn is_nifu(&self, file: i8) -> bool {
+        let example_pawn = Place(self.stm, PieceType::Pawn);
+        (0..=8)
+            .map(|rank| Square::new(file, rank).unwrap())
+            .any(|sq| self.board[sq.to_index()] == example_pawn)
+    }
+
+    // Rank of `sq` counted from the far side of the board as seen by `color`.
+    fn relative_rank(&self, sq: Square, color: Color) -> i8 {
+        match color {
+            Color::Sente => sq.rank(),
+            Color::Gote => self.variant.size() - 1 - sq.rank(),
+        }
+    }
+
+    fn is_on_board(&self, sq: Square) -> bool {
+        sq.file() < self.variant.size() && sq.rank() < self.variant.size()
+    }
+
+    pub fn is_promo_square(&self, sq: Square, color: Color) -> bool {
+        self.relative_rank(sq, color) < self.variant.promotion_ranks()
+    }
+
+    // 行き所のない駒
+    fn is_ikidokorononai(&self, color: Color, pt: PieceType, sq: Square) -> bool {
+        let rank = self.relative_rank(sq, color);
+        match pt {
+            PieceType::None => true,
+            PieceType::Pawn | PieceType::Lance => rank <= 0,
+            PieceType::Knight => rank <= 1,
+            _ => false,
+        }
+    }
+
+    pub fn is_legal(&self, m: Move) -> bool {
+        self.do_move(m).is_some()
+    }
+
+    pub fn do_move(&self, m: Move) -> Option<Position> {
+        let mut new_pos = *self;
+        match m {
+            Move::None => return None,
+            Move::Win => return None,
+            Move::Resign => return None,
+            Move::Drop(ptype, sq) => {
+                if !self.is_empty(sq)
+                    || !self.is_on_board(sq)
+                    // Invalid piece type to drop
+                    || ptype.promoted() || ptype == PieceType::None || ptype == PieceType::King
+                    // Illegal move rule: 二歩
+                    || (ptype == PieceType::Pawn && self.is_nifu(sq.file()))
+                    // Illegal move rule: 行き所のない駒
+                    || self.is_ikidokorononai(self.stm, ptype, sq)
+                {
+                    return None;
+                }
+
+                let piece_in_hand = self.hand[self.stm.to_index()].get(ptype);
+                if piece_in_hand == 0 {
+                    return None;
+                }
+                new_pos.set_hand_count(self.stm, ptype, piece_in_hand - 1);
+
+                new_pos.set_place(sq, Place(new_pos.stm, ptype));
+            }
+            Move::Normal { from, to, promo } => {
+                let is_capture = !self.is_empty(to);
+                let ptype = self.board[from.to_index()].1;
+
+                // Invalid moves
+                if from == to {
+                    return None;
+                }
+                if !self.is_friendly(from) || !self.is_on_board(to) {
+                    return None;
+                }
+                if promo
+                    && !self.is_promo_square(from, self.stm)
+                    && !self.is_promo_square(to, self.stm)
+                {
+                    return None;
+                }
+                if promo && !ptype.promotable() {
+                    return None;
+                }
+                if is_capture && !self.is_enemy(to) {
+                    return None;
+                }
+
+                // Illegal move rule: 行き所のない駒
+                if !promo && self.is_ikidokorononai(self.stm, ptype, to) {
+                    return None;
+                }
+
+                // Check piece movement
+                if !self.piece_has_ray_to(from, to) {
+                    return None;
+                }
+
+                // Move on board
+                let place = Place(self.stm, if promo { ptype.promote() } else { ptype });
+                if is_capture {
+                    let hand_ptype = self.board[to.to_index()].1.demote();
+                    let piece_in_hand = self.hand[self.stm.to_index()].get(hand_ptype);
+                    new_pos.set_hand_count(self.stm, hand_ptype, piece_in_hand + 1);
+                }
+                new_pos.set_place(to, place);
+                new_pos.set_place(from, Place::default());
+            }
+        }
+
+        // Check if we left ourselves in check
+        if new_pos.is_in_check() {
+            return None;
+        }
+
+        new_pos.stm = !new_pos.stm;
+        new_pos.key ^= zobrist::side_key();
+        new_pos.ply += 1;
+
+        // Illegal move rule: 打ち歩詰め
+        if let Move::Drop(PieceType::Pawn, _) = m
+            && new_pos.get_check_state() == CheckState::Checkmate
+        {
+            return None;
+        }
+
+        Some(new_pos)
+    }
+
+    pub fn piece_has_ray_to(&self, from: Square, to: Square) -> bool {
+        let place = self.board[from.to_index()];
+        let delta = to - from;
+        let delta_valid = delta.could_be_piece_move(place.0, place.1);
+        match place.1 {
+            PieceType::None => false,
+            PieceType::Knight => delta_valid,
+            _ => delta_valid && self.is_clear_between(from, to),
+        }
+    }
+
+    fn is_clear_between(&self, a: Square, b: Square) -> bool {
+        let delta = b - a;
+        assert!(a != b);
+        assert!(delta.file == 0 || delta.rank == 0 || delta.file.abs() == delta.rank.abs());
+        let step = delta.signum();
+        let mut current = (a + step).unwrap();
+        while current != b {
+            if !self.is_empty(current) {
+                return false;
+            }
+            current = (current + step).unwrap();
+        }
+        true
+    }
+
+    pub fn parse(s: &str) -> Option<Position> {
+        let mut it = s.split(' ');
+        let board = it.next()?;
+        let color = it.next()?;
+        let hand = it.next()?;
+        let ply = it.next()?;
+        if it.next().is_none() {
+            Position::parse_parts(board, color, hand, ply)
+        } else {
+            None
+        }
+    }
+
+    /// Parses the parts of an SFEN. The variant is inferred from the number of ranks.
+    pub fn parse_parts(board: &str, color: &str, hand: &str, ply: &str) -> Option<Position> {
+        let (variant, board) = Position::parse_board(board)?;
+        let hand = Position::parse_hand(hand)?;
+        let board_pieces = board.iter().map(|place| place.1);
+        let hand_pieces = HAND_PTYPES
+            .iter()
+            .copied()
+            .filter(|&pt| hand.iter().any(|h| h.get(pt) != 0));
+        if !board_pieces
+            .chain(hand_pieces)
+            .all(|pt| pt == PieceType::None || variant.has_piece(pt))
+        {
+            return None;
+        }
+        Some(Position::new(
+            variant,
+            board,
+            hand,
+            Color::parse(color)?,
+            ply.parse().ok()?,
+        ))
+    }
+
+    fn parse_board(s: &str) -> Option<(Variant, [Place; 81])> {
+        let mut board = [Place(Color::Sente, PieceType::None); 81];
+
+        let variant = Variant::from_size(s.split('/').count() as i8)?;
+        let size = variant.size();
+        let squares = (size as usize).pow(2);
+        let board_str = s.as_bytes();
+        let mut place_index: usize = 0;
+        let mut i: usize = 0;
+
+        while place_index < squares && i < board_str.len() {
+            let sq = Square::from_fen_ordering(place_index, size).unwrap();
+            let ch = board_str[i];
+            match ch {
+                b'/' => {
+                    if sq.file() != size - 1 || place_index == 0 {
+                        return None;
+                    }
+                    i += 1;
+                    continue;
+                }
+                b'1'..=b'9' => {
+                    place_index += (ch - b'0') as usize;
+                    i += 1;
+                    continue;
+                }
+                b'p' => board[sq.to_index()] = Place(Color::Gote, PieceType::Pawn),
+                b'b' => board[sq.to_index()] = Place(Color::Gote, PieceType::Bishop),
+                b'r' => board[sq.to_index()] = Place(Color::Gote, PieceType::Rook),
+                b'l' => board[sq.to_index()] = Place(Color::Gote, PieceType::Lance),
+                b'n' => board[sq.to_index()] = Place(Color::Gote, PieceType::Knight),
+                b's' => board[sq.to_index()] = Place(Color::Gote, PieceType::Silver),
+                b'g' => board[sq.to_index()] = Place(Color::Gote, PieceType::Gold),
+                b'k' => board[sq.to_index()] = Place(Color::Gote, PieceType::King),
+                b'P' => board[sq.to_index()] = Place(Color::Sente, PieceType::Pawn),
+                b'B' => board[sq.to_index()] = Place(Color::Sente, PieceType::Bishop),
+                b'R' => board[sq.to_index()] = Place(Color::Sente, PieceType::Rook),
+                b'L' => board[sq.to_index()] = Place(Color::Sente, PieceType::Lance),
+                b'N' => board[sq.to_index()] = Place(Color::Sente, PieceType::Knight),
+                b'S' => board[sq.to_index()] = Place(Color::Sente, PieceType::Silver),
+                b'G' => board[sq.to_index()] = Place(Color::Sente, PieceType::Gold),
+                b'K' => board[sq.to_index()] = Place(Color::Sente, PieceType::King),
+                b'+' => {
+                    i += 1;
+                    if i >= board_str.len() {
+                        return None;
+                    }
+                    match board_str[i] {
+                        b'p' => board[sq.to_index()] = Place(Color::Gote, PieceType::Tokin),
+                        b'b' => board[sq.to_index()] = Place(Color::Gote, PieceType::Horse),
+                        b'r' => board[sq.to_index()] = Place(Color::Gote, PieceType::Dragon),
+                        b'l' => board[sq.to_index()] = Place(Color::Gote, PieceType::NariLance),
+                        b'n' => board[sq.to_index()] = Place(Color::Gote, PieceType::NariKnight),
+                        b's' => board[sq.to_index()] = Place(Color::Gote, PieceType::NariSilver),
+                        b'P' => board[sq.to_index()] = Place(Color::Sente, PieceType::Tokin),
+                        b'B' => board[sq.to_index()] = Place(Color::Sente, PieceType::Horse),
+                        b'R' => board[sq.to_index()] = Place(Color::Sente, PieceType::Dragon),
+                        b'L' => board[sq.to_index()] = Place(Color::Sente, PieceType::NariLance),
+                        b'N' => board[sq.to_index()] = Place(Color::Sente, PieceType::NariKnight),
+                        b'S' => board[sq.to_index()] = Place(Color::Sente, PieceType::NariSilver),
+                        _ => return None,
+                    }
+                }
+                _ => return None,
+            }
+            i += 1;
+            place_index += 1;
+        }
+
+        if place_index != squares || i != board_str.len() {
+            return None;
+        }
+
+        Some((variant, board))
+    }
+
+    fn parse_hand(s: &str) -> Option<[Hand; 2]> {
+        let mut hand = [Hand::default(); 2];
+
+        if s == "-" {
+            return Some(hand);
+        }
+
+        let mut modifier: Option<usize> = None;
+
+        for ch in s.bytes() {
+            match ch {
+                b'0'..=b'9' => {
+                    if modifier.is_none() && ch == b'0' {
+                        return None;
+                    }
+                    modifier = Some(modifier.unwrap_or(0) * 10 + (ch - b'0') as usize);
+                    if modifier > Some(18) {
+                        return None;
+                    }
+                    continue;
+                }
+                b'p' => hand[Color::Gote.to_index()].set_from_parse(PieceType::Pawn, modifier),
+                b'b' => hand[Color::Gote.to_index()].set_from_parse(PieceType::Bishop, modifier),
+                b'r' => hand[Color::Gote.to_index()].set_from_parse(PieceType::Rook, modifier),
+                b'l' => hand[Color::Gote.to_index()].set_from_parse(PieceType::Lance, modifier),
+                b'n' => hand[Color::Gote.to_index()].set_from_parse(PieceType::Knight, modifier),
+                b's' => hand[Color::Gote.to_index()].set_from_parse(PieceType::Silver, modifier),
+                b'g' => hand[Color::Gote.to_index()].set_from_parse(PieceType::Gold, modifier),
+                b'P' => hand[Color::Sente.to_index()].set_from_parse(PieceType::Pawn, modifier),
+                b'B' => hand[Color::Sente.to_index()].set_from_parse(PieceType::Bishop, modifier),
+                b'R' => hand[Color::Sente.to_index()].set_from_parse(PieceType::Rook, modifier),
+                b'L' => hand[Color::Sente.to_index()].set_from_parse(PieceType::Lance, modifier),
+                b'N' => hand[Color::Sente.to_index()].set_from_parse(PieceType::Knight, modifier),
+                b'S' => hand[Color::Sente.to_index()].set_from_parse(PieceType::Silver, modifier),
+                b'G' => hand[Color::Sente.to_index()].set_from_parse(PieceType::Gold, modifier),
+                _ => return None,
+            }
+            modifier = None;
+        }
+
+        if modifier.is_some() {
+            return None;
+        }
+
+        Some(hand)
+    }
+
+    /// Number of leaf positions of the legal move tree `depth` plies deep.
+    pub fn perft(&self, depth: usize) -> u64 {
+        if depth == 0 {
+            return 1;
+        }
+
+        let moves = self.legal_moves();
+        if depth == 1 {
+            return moves.len() as u64;
+        }
+
+        moves
+            .iter()
+            .map(|&m| self.do_move(m).unwrap().perft(depth - 1))
+            .sum()
+    }
+
+    /// Perft split by the legal moves of this position.
+    pub fn divide(&self, depth: usize) -> Vec<(Move, u64)> {
+        self.legal_moves()
+            .iter()
+            .map(|&m| (m, self.do_move(m).unwrap().perft(depth.saturating_sub(1))))
+            .collect()
+    }
+}
+
+impl fmt::Display for Position {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        // Board
+        {
+            let size = self.variant.size();
+            let squares = (size as usize).pow(2);
+            let mut blanks = 0;
+            for i in 0..squares {
+                let sq = Square::from_fen_ordering(i, size).unwrap();
+                let place = self.board[sq.to_index()];
+                if place.is_empty() {
+                    blanks += 1;
+                } else {
+                    if blanks != 0 {
+                        write!(f, "{blanks}")?;
+                        blanks = 0;
+                    }
+                    write!(f, "{place}")?;
+                }
+                if sq.file() == 0 {
+                    if blanks != 0 {
+                        write!(f, "{blanks}")?;
+                        blanks = 0;
+                    }
+                    if i != squares - 1 {
+                        write!(f, "/")?;
+                    }
+                }
+            }
+        }
+
+        // Side to move
+        write!(f, " {} ", self.stm)?;
+
+        // Hand
+        {
+            let mut wrote_hand = false;
+            let mut op = |count, ch| {
+                if count != 0 {
+                    wrote_hand = true;
+                    if count == 1 {
+                        write!(f, "{ch}")
+                    } else {
+                        write!(f, "{count}{ch}")
+                    }
+                } else {
+                    Ok(())
+                }
+            };
+
+            op(self.hand[0].rook, 'R')?;
+            op(self.hand[0].bishop, 'B')?;
+            op(self.hand[0].gold, 'G')?;
+            op(self.hand[0].silver, 'S')?;
+            op(self.hand[0].knight, 'N')?;
+            op(self.hand[0].lance, 'L')?;
+            op(self.hand[0].pawn, 'P')?;
+            op(self.hand[1].rook, 'r')?;
+            op(self.hand[1].bishop, 'b')?;
+            op(self.hand[1].gold, 'g')?;
+            op(self.hand[1].silver, 's')?;
+            op(self.hand[1].knight, 'n')?;
+            op(self.hand[1].lance, 'l')?;
+            op(self.hand[1].pawn, 'p')?;
+
+            if !wrote_hand {
+                write!(f, "-")?;
+            }
+        }
+
+        // Ply
+        write!(f, " {}", self.ply)?;
+
+        Ok(())
+    }
+}
+
+#[derive(Copy, Clone, PartialEq, Eq, Debug)]
+pub enum GameOutcome {
+    Undetermined,
+    Checkmated(Color),
+    WinInImpasse(Color),
+    DrawBySennichite,
+    LossBySennichite(Color),
+    LossByPerpetual(Color),
+    LossByIllegal(Color),
+    Resignation(Color),
+    LossByClock(Color),
+    LossByDisconnection(Color),
+    DrawByMoveLimit,
+    DrawByAdjudication,
+    WinByAdjudication(Color),
+    DrawByImpasse,
+    WinByForcedMate(Color),
+    DrawByPerpetual(Color),
+    DrawByRepetitionAdjudication,
+}
+
+impl GameOutcome {
+    pub fn is_determined(self) -> bool {
+        self != GameOutcome::Undetermined
+    }
+
+    pub fn winner(self) -> Option<Color> {
+        match self {
+            GameOutcome::Undetermined => None,
+            GameOutcome::Checkmated(color) => Some(!color),
+            GameOutcome::WinInImpasse(color) => Some(color),
+            GameOutcome::DrawBySennichite => None,
+            GameOutcome::LossBySennichite(color) => Some(!color),
+            GameOutcome::LossByPerpetual(color) => Some(!color),
+            GameOutcome::LossByIllegal(color) => Some(!color),
+            GameOutcome::Resignation(color) => Some(!color),
+            GameOutcome::LossByClock(color) => Some(!color),
+            GameOutcome::LossByDisconnection(color) => Some(!color),
+            GameOutcome::DrawByMoveLimit => None,
+            GameOutcome::DrawByAdjudication => None,
+            GameOutcome::WinByAdjudication(color) => Some(color),
+            GameOutcome::DrawByImpasse => None,
+            GameOutcome::WinByForcedMate(color) => Some(color),
+            GameOutcome::DrawByPerpetual(_) => None,
+            GameOutcome::DrawByRepetitionAdjudication => None,
+        }
+    }
+
+    pub fn is_draw(self) -> bool {
+        self.is_determined() && self.winner().is_none()
+    }
+
+    pub fn to_string(self) -> &'static str {
+        match self {
+            GameOutcome::Undetermined => "Undetermined (Game is still in play)",
+            GameOutcome::DrawBySennichite => "Draw by repetition",
+            GameOutcome::LossBySennichite(Color::Sente) => "Sente loses by repetition",
+            GameOutcome::LossBySennichite(Color::Gote) => "Gote loses by repetition",
+            GameOutcome::Checkmated(Color::Sente) => "Gote mates",
+            GameOutcome::Checkmated(Color::Gote) => "Sente mates",
+            GameOutcome::WinInImpasse(Color::Sente) => "Sente wins by impasse",
+            GameOutcome::WinInImpasse(Color::Gote) => "Gote wins by impasse",
+            GameOutcome::LossByPerpetual(Color::Sente) => "Sente makes an illegal perpetual check",
+            GameOutcome::LossByPerpetual(Color::Gote) => "Gote makes an illegal perpetual check",
+            GameOutcome::LossByIllegal(Color::Sente) => "Sente makes an illegal move",
+            GameOutcome::LossByIllegal(Color::Gote) => "Gote makes an illegal move",
+            GameOutcome::Resignation(Color::Sente) => "Sente resigns",
+            GameOutcome::Resignation(Color::Gote) => "Gote resigns",
+            GameOutcome::LossByClock(Color::Sente) => "Sente loses on time",
+            GameOutcome::LossByClock(Color::Gote) => "Gote loses on time",
+            GameOutcome::LossByDisconnection(_) => "abandoned",
+            GameOutcome::DrawByMoveLimit => "Draw by adjudication: Reached move limit",
+            GameOutcome::DrawByAdjudication => "Draw by adjudication",
+            GameOutcome::WinByAdjudication(Color::Sente) => "Sente wins by adjudication",
+            GameOutcome::WinByAdjudication(Color::Gote) => "Gote wins by adjudication",
+            GameOutcome::DrawByImpasse => "Draw by impasse",
+            GameOutcome::WinByForcedMate(Color::Sente) => "Sente wins by adjudication: forced mate",
+            GameOutcome::WinByForcedMate(Color::Gote) => "Gote wins by adjudication: forced mate",
+            GameOutcome::DrawByPerpetual(Color::Sente) => "Draw by perpetual check from Sente",
+            GameOutcome::DrawByPerpetual(Color::Gote) => "Draw by perpetual check from Gote",
+            GameOutcome::DrawByRepetitionAdjudication => "Draw by adjudication: Repeated position",
+        }
+    }
+
+    pub fn to_pgn_termination_string(self) -> &'static str {
+        match self {
+            GameOutcome::Undetermined => "unterminated",
+            GameOutcome::DrawBySennichite => "normal",
+            GameOutcome::LossBySennichite(_) => "normal",
+            GameOutcome::Checkmated(_) => "normal",
+            GameOutcome::WinInImpasse(_) => "normal",
+            GameOutcome::LossByPerpetual(_) => "illegal move",
+            GameOutcome::LossByIllegal(_) => "illegal move",
+            GameOutcome::Resignation(_) => "normal",
+            GameOutcome::LossByClock(_) => "time forfeit",
+            GameOutcome::LossByDisconnection(_) => "abandoned",
+            GameOutcome::DrawByMoveLimit => "adjudication",
+            GameOutcome::DrawByAdjudication => "adjudication",
+            GameOutcome::WinByAdjudication(_) => "adjudication",
+            GameOutcome::DrawByImpasse => "normal",
+            GameOutcome::WinByForcedMate(_) => "adjudication",
+            GameOutcome::DrawByPerpetual(_) => "normal",
+            GameOutcome::DrawByRepetitionAdjudication => "adjudication",
+        }
+    }
+}
+
+/// How a declaration of a win by impasse (入玉宣言) is judged.
+#[derive(Copy, Clone, PartialEq, Eq, Debug, Default)]
+pub enum ImpasseRule {
+    /// 27-point rule: the declaring side wins with 28 (Sente) or 27 (Gote) points.
+    #[default]
+    Points27,
+    /// 24-point rule: 31 points or more wins, 24 to 30 points is a draw if the opponent also has
+    /// 24 points and a win otherwise.
+    Points24,
+    /// Try rule (トライルール): moving the king onto the opponent king's starting square wins.
+    Try,
+    /// Declarations are not allowed.
+    None,
+}
+
+/// How a repetition caused by one side checking on every move is judged.
+#[derive(Copy, Clone, PartialEq, Eq, Debug, Default)]
+pub enum PerpetualRule {
+    /// The checking side loses (連続王手の千日手).
+    #[default]
+    Loss,
+    /// The game is drawn like any other repetition.
+    Draw,
+}
+
+/// How a repetition that is not a perpetual check is judged.
+#[derive(Copy, Clone, PartialEq, Eq, Debug)]
+pub enum RepetitionRule {
+    /// The game is drawn (千日手).
+    Draw,
+    /// The side that first returned to an earlier position loses.
+    Loss,
+}
+
+/// Rules that vary between tournaments.
+#[derive(Copy, Clone, PartialEq, Eq, Debug)]
+pub struct GameRules {
+    pub impasse: ImpasseRule,
+    /// Number of occurrences of the same position that ends the game. The official rule is 4;
+    /// anything lower ends the game early by adjudication.
+    pub repetitions: usize,
+    pub perpetual: PerpetualRule,
+    /// None follows the variant: a loss for Sente in minishogi and a draw otherwise.
+    pub repetition: Option<RepetitionRule>,
+}
+
+impl Default for GameRules {
+    fn default() -> Self {
+        GameRules {
+            impasse: ImpasseRule::default(),
+            repetitions: 4,
+            perpetual: PerpetualRule::default(),
+            repetition: None,
+        }
+    }
+}
+
+#[derive(Debug, Clone)]
+pub struct Game {
+    rules: GameRules,
+    current_position: Position,
+    moves: Vec<Move>,
+    history: Vec<Position>,
+    // Zobrist key -> (number of occurrences, history index of first occurrence)
+    repetitions: HashMap<u64, (usize, usize)>,
+    last_not_in_check_ply: [isize; 2],
+}
+
+impl Game {
+    pub fn new(startpos: Position) -> Game {
+        Game::with_rules(startpos, GameRules::default())
+    }
+
+    pub fn with_rules(startpos: Position, rules: GameRules) -> Game {
+        // assert!(!startpos.is_in_check());
+        Game {
+            rules,
+            current_position: startpos,
+            moves: vec![],
+            history: vec![startpos],
+            repetitions: HashMap::from([(startpos.key(), (1, 0))]),
+            last_not_in_check_ply: [-1, -1],
+        }
+    }
+
+    pub fn stm(&self) -> Color {
+        self.current_position.stm
+    }
+
+    pub fn position(&self) -> &Position {
+        &self.current_position
+    }
+
+    /// Every position of the game so far, starting with the start position.
+    pub fn history(&self) -> &[Position] {
+        &self.history
+    }
+
+    pub fn usi_string(&self) -> String {
+        let mut string = format!("sfen {}", self.history[0]);
+        if !self.moves.is_empty() {
+            string += " moves";
+            string.extend(self.moves.iter().map(|m| format!(" {m}")));
+        }
+        string
+    }
+
+    /// Parses the arguments of a USI `position` command ("startpos" or "sfen SFEN", optionally
+    /// followed by "moves" and a move list), as produced by `usi_string`. A leading "position" is
+    /// accepted and "startpos" is the starting position of `variant`. Every move is played through
+    /// `do_move`, so illegal moves are rejected and the repetition state matches a game that was
+    /// played out.
+    pub fn from_usi(s: &str, variant: Variant, rules: GameRules) -> Result<Game, String> {
+        let mut it = s.split_whitespace().peekable();
+        it.next_if_eq(&"position");
+
+        let startpos = match it.next() {
+            Some("startpos") => variant.startpos(),
+            Some("sfen") => {
+                let parts: Vec<&str> = it.by_ref().take(4).collect();
+                let sfen = parts.join(" ");
+                match parts[..] {
+                    [board, color, hand, ply] => Position::parse_parts(board, color, hand, ply),
+                    _ => None,
+                }
+                .ok_or_else(|| format!("Invalid sfen: {sfen}"))?
+            }
+            Some(token) => return Err(format!("Expected startpos or sfen, found {token}")),
+            None => return Err("Empty position".to_string()),
+        };
+        if let Some(violation) = startpos.validate().first() {
+            return Err(format!("Invalid position {startpos}: {violation}"));
+        }
+
+        let mut game = Game::with_rules(startpos, rules);
+        match it.next() {
+            Some("moves") => {}
+            Some(token) => return Err(format!("Expected moves, found {token}")),
+            None => return Ok(game),
+        }
+
+        let mut outcome = GameOutcome::Undetermined;
+        for (i, mstr) in it.enumerate() {
+            if outcome.is_determined() {
+                return Err(format!(
+                    "Move {} ({mstr}) is played after the game ended",
+                    i + 1
+                ));
+            }
+            let m = match Move::parse(mstr) {
+                Some(m @ (Move::Normal { .. } | Move::Drop(..))) => m,
+                _ => return Err(format!("Move {} ({mstr}) is not a valid move", i + 1)),
+            };
+            outcome = game.do_move(m);
+            if let GameOutcome::LossByIllegal(_) = outcome {
+                return Err(format!("Move {} ({mstr}) is illegal", i + 1));
+            }
+        }
+        Ok(game)
+    }
+
+    pub fn do_move(&mut self, m: Move) -> GameOutcome {
+        let stm = self.current_position.stm;
+
+        self.moves.push(m);
+
+        if m == Move::Resign {
+            return GameOutcome::Resignation(stm);
+        }
+
+        if m == Move::Win {
+            return self.impasse_declaration();
+        }
+
+        if let Some(next_position) = self.current_position.do_move(m) {
+            self.current_position = next_position;
+            self.history.push(next_position);
+        } else {
+            return GameOutcome::LossByIllegal(stm);
+        }
+
+        assert!(stm != self.current_position.stm);
+        let stm = self.current_position.stm;
+
+        match self.current_position.get_check_state() {
+            CheckState::None => {
+                self.last_not_in_check_ply[stm.to_index()] = (self.history.len() - 1) as isize
+            }
+            CheckState::Check => {}
+            CheckState::Checkmate => return GameOutcome::Checkmated(stm),
+        }
+
+        if self.rules.impasse == ImpasseRule::Try
+            && let Move::Normal { to, .. } = m
+            && to == self.try_square(!stm)
+            && self.current_position.king_square(!stm) == Some(to)
+        {
+            return GameOutcome::WinInImpasse(!stm);
+        }
+
+        let (num_clones, first_clone) = {
+            let ply = self.history.len() - 1;
+            let entry = self
+                .repetitions
+                .entry(self.current_position.key())
+                .or_insert((0, ply));
+            if !self.history[entry.1].is_clone_of(&self.current_position) {
+                // A different position with the same key is not a repetition
+                return GameOutcome::Undetermined;
+            }
+            entry.0 += 1;
+            (entry.0, entry.1 as isize)
+        };
+
+        let perpetual = |checker: Color| match self.rules.perpetual {
+            PerpetualRule::Loss => GameOutcome::LossByPerpetual(checker),
+            PerpetualRule::Draw => GameOutcome::DrawByPerpetual(checker),
+        };
+
+        if num_clones < self.rules.repetitions {
+            GameOutcome::Undetermined
+        } else if self.last_not_in_check_ply[stm.to_index()] < first_clone {
+            perpetual(!stm)
+        } else if self.last_not_in_check_ply[(!stm).to_index()] < first_clone {
+            perpetual(stm)
+        } else if self.rules.repetition.is_none()
+            && self.current_position.variant == Variant::Minishogi
+        {
+            GameOutcome::LossBySennichite(Color::Sente)
+        } else if self.rules.repetition == Some(RepetitionRule::Loss) {
+            // The repeated position comes first in the cycle, so the side that just moved into it
+            // is the one that returned to it first
+            GameOutcome::LossBySennichite(!stm)
+        } else if num_clones < 4 {
+            GameOutcome::DrawByRepetitionAdjudication
+        } else {
+            GameOutcome::DrawBySennichite
+        }
+    }
+
+    // The starting square of the opponent's king, which `color`'s king has to reach under the
+    // try rule.
+    fn try_square(&self, color: Color) -> Square {
+        let variant = self.current_position.variant;
+        variant.startpos().king_sq(!color)
+    }
+
+    // Judge a declaration of a win by impasse by the side to move.
+    fn impasse_declaration(&self) -> GameOutcome {
+        let stm = self.current_position.stm;
+        let pos = self.current_position;
+
+        match self.rules.impasse {
+            ImpasseRule::None => return GameOutcome::LossByIllegal(stm),
+            ImpasseRule::Try => {
+                // Declaring is only valid if the king can legally move onto the try square
+                let try_move = Move::Normal {
+                    from: pos.king_sq(stm),
+                    to: self.try_square(stm),
+                    promo: false,
+                };
+                return if pos.is_legal(try_move) {
+                    GameOutcome::WinInImpasse(stm)
+                } else {
+                    GameOutcome::LossByIllegal(stm)
+                };
+            }
+            ImpasseRule::Points27 | ImpasseRule::Points24 => {}
+        }
+
+        let ptype_points = |pt: PieceType| match pt.demote() {
+            PieceType::None => 0,
+            PieceType::King => 0,
+            PieceType::Rook | PieceType::Bishop => 5,
+            PieceType::Pawn
+            | PieceType::Lance
+            | PieceType::Knight
+            | PieceType::Silver
+            | PieceType::Gold => 1,
+            _ => panic!("should be unreachable"),
+        };
+
+        if !pos.is_promo_square(pos.king_sq(stm), stm) {
+            return GameOutcome::LossByIllegal(stm);
+        }
+        if pos.is_in_check() {
+            return GameOutcome::LossByIllegal(stm);
+        }
+
+        // Every piece on the board counts, wherever it is, and the king counts as one of the ten
+        let pieces = |color: Color| {
+            (0..81)
+                .map(Square)
+                .filter_map(|sq| pos.piece_at(sq))
+                .filter(move |&(c, _)| c == color)
+                .map(|(_, pt)| pt)
+        };
+        if pieces(stm).count() < 10 {
+            return GameOutcome::LossByIllegal(stm);
+        }
+
+        let points = |color: Color| -> usize {
+            pieces(color).map(ptype_points).sum::<usize>()
+                + HAND_PTYPES
+                    .iter()
+                    .map(|&pt| pos.hand[color.to_index()].get(pt) as usize * ptype_points(pt))
+                    .sum::<usize>()
+        };
+        let opponent_points = points(!stm);
+        let points = points(stm);
+
+        match (self.rules.impasse, stm) {
+            (ImpasseRule::Points27, Color::Sente) if points >= 28 => GameOutcome::WinInImpasse(stm),
+            (ImpasseRule::Points27, Color::Gote) if points >= 27 => GameOutcome::WinInImpasse(stm),
+            (ImpasseRule::Points24, _) if points >= 31 => GameOutcome::WinInImpasse(stm),
+            // A draw needs both sides to have 24 points; an opponent short of them loses
+            (ImpasseRule::Points24, _) if points >= 24 && opponent_points >= 24 => {
+                GameOutcome::DrawByImpasse
+            }
+            (ImpasseRule::Points24, _) if points >= 24 => GameOutcome::WinInImpasse(stm),
+            _ => GameOutcome::LossByIllegal(stm),
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use itertools::iproduct;
+    use lazy_static::lazy_static;
+    use std::collections::HashSet;
+
+    lazy_static! {
+        static ref ALL_MOVES: Vec<Move> = {
+            let sq: Vec<Square> = (0..81).map(Square).collect();
+            let drop_ptypes: Vec<PieceType> = vec![
+                PieceType::Pawn,
+                PieceType::Bishop,
+                PieceType::Rook,
+                PieceType::Lance,
+                PieceType::Knight,
+                PieceType::Silver,
+                PieceType::Gold,
+            ];
+            let bools: Vec<bool> = vec![false, true];
+            let mut res = Vec::<Move>::new();
+            res.extend(
+                iproduct!(&sq, &sq, &bools).map(|(&from, &to, &promo)| Move::Normal {
+                    from,
+                    to,
+                    promo,
+                }),
+            );
+            res.extend(iproduct!(&drop_ptypes, &sq).map(|(&pt, &sq)| Move::Drop(pt, sq)));
+            res
+        };
+    }
+
+    // Reference implementation: try every conceivable move through `do_move`.
+    fn brute_force_legal_moves(position: &Position) -> Vec<Move> {
+        ALL_MOVES
+            .iter()
+            .filter(|&&m| position.is_legal(m))
+            .copied()
+            .collect()
+    }
+
+    fn assert_movegen_matches_brute_force(position: &Position) {
+        let key = |m: &Move| format!("{m}");
+        let mut expected = brute_force_legal_moves(position);
+        let mut actual = position.legal_moves().to_vec();
+        expected.sort_by_key(key);
+        actual.sort_by_key(key);
+        assert_eq!(expected, actual, "{position}");
+    }
+
+    #[test]
+    fn move_parse_test() {
+        assert_eq!(
+            Move::parse("1a2b+").unwrap(),
+            Move::Normal {
+                from: Square::parse(b'1', b'a').unwrap(),
+                to: Square::parse(b'2', b'b').unwrap(),
+                promo: true
+            }
+        )
+    }
+
+    #[test]
+    fn roundtrip_fens() {
+        let cases = vec![
+            "lnsgkgsn1/1r5b1/ppppppppp/9/9/9/PPPPPPPPP/1B5R1/LNSGKGSNL w - 1",
+            "lnsgk2nl/1r4gs1/p1pppp1pp/1p4p2/7P1/2P6/PP1PPPP1P/1SG4R1/LN2KGSNL b Bb 1",
+            "ln1g5/1r2S1k2/p2pppn2/2ps2p2/1p7/2P6/PPSPPPPLP/2G2K1pr/LN4G1b w BGSLPnp 62",
+            "8l/1l+R2P3/p2pBG1pp/kps1p4/Nn1P2G2/P1P1P2PP/1PS6/1KSG3+r1/LN2+p3L w Sbgn3p 124",
+            "ln1g3nl/2s2k1+P1/p3pg3/1np2p2p/3p5/1SP3P1P/P1KPPSp2/2G6/L2b1G2L w RBSN2Pr2p 66",
+        ];
+        for case in cases {
+            let position = Position::parse(case).unwrap();
+            let sfen = format!("{position}");
+            assert_eq!(case, sfen);
+        }
+    }
+
+    #[test]
+    fn uchifuzume() {
+        let cases = vec![
+            ("9/9/7gp/7pk/9/7G1/9/PPPPPPPP1/K8 b P 1", true, "P*1e"),
+            ("9/9/7pp/7sk/9/7G1/9/PPPPPPPP1/K8 b P 1", false, "P*1e"),
+            ("9/9/8p/6K1k/9/7G1/9/PPPPPPPP1/9 b P 1", true, "P*1e"),
+            ("9/9/8p/6K1k/9/9/9/PPPPPPPP1/9 b P 1", false, "P*1e"),
+            ("9/9/7gp/1R5gk/9/7G1/9/PPPPPPPP1/K8 b P 1", true, "P*1e"),
+            ("9/9/7gp/7gk/9/7G1/9/PPPPPPPP1/K8 b P 1", false, "P*1e"),
+        ];
+        for (sfen, is_uchifuzume, mstr) in cases {
+            let position = Position::parse(sfen).unwrap();
+            let m = Move::parse(mstr).unwrap();
+            assert_eq!(is_uchifuzume, !position.is_legal(m));
+        }
+    }
+
+    #[test]
+    #[allow(clippy::bool_assert_comparison)]
+    fn remember_to_promote() {
+        let sfen = "lnsgkgsnl/1r5b1/ppppppppp/9/9/9/PPPPPPPPP/1B5R1/LNSGKGSNL b - 1";
+        let position = Position::parse(sfen).unwrap();
+        let position = position.do_move(Move::parse("7g7f").unwrap()).unwrap();
+        let position = position.do_move(Move::parse("5a4b").unwrap()).unwrap();
+        let position = position.do_move(Move::parse("8h3c+").unwrap()).unwrap();
+        assert_eq!(false, position.is_legal(Move::parse("4b3b").unwrap()));
+    }
+
+    #[test]
+    fn captures_add_pieces_to_hand() {
+        let sfen = "8l/1l+R2P3/p2pBG1pp/kps1p4/Nn1P2G2/P1P1P2PP/1PS6/1KSG3+r1/LN2+p3L w Sbgn3p 124";
+        let position = Position::parse(sfen).unwrap();
+        let position = position.do_move(Move::parse("2h1i").unwrap()).unwrap();
+        assert_eq!(22380, position.perft(2));
+    }
+
+    fn test_perft(sfen: &str, numbers: Vec<u64>) {
+        let position = Position::parse(sfen).unwrap();
+        for (depth, number) in numbers.iter().enumerate() {
+            println!("Testing perft on {sfen} depth={depth} number={number}");
+            assert_eq!(*number, position.perft(depth));
+        }
+    }
+
+    #[test]
+    fn test_perft_startpos() {
+        test_perft(
+            "lnsgkgsnl/1r5b1/ppppppppp/9/9/9/PPPPPPPPP/1B5R1/LNSGKGSNL b - 1",
+            vec![1, 30, 900, 25470, 719731],
+        );
+    }
+
+    #[test]
+    fn test_perft_matsuri() {
+        test_perft(
+            "l6nl/5+P1gk/2np1S3/p1p4Pp/3P2Sp1/1PPb2P1P/P5GS1/R8/LN4bKL w GR5pnsg 1",
+            vec![1, 207, 28684, 4809015],
+        );
+    }
+
+    #[test]
+    fn test_perft_pos1() {
+        test_perft(
+            "8l/1l+R2P3/p2pBG1pp/kps1p4/Nn1P2G2/P1P1P2PP/1PS6/1KSG3+r1/LN2+p3L w Sbgn3p 124",
+            vec![1, 178, 18041, 2552846],
+        );
+    }
+
+    #[test]
+    fn test_perft_alldrops() {
+        test_perft(
+            "9/9/9/3k5/9/5K3/9/9/9 b RB2G2S2N2L9Prb2g2s2n2l9p 1",
+            vec![1, 524, 248257], // 112911856
+        );
+    }
+
+    #[test]
+    fn test_perft_minishogi() {
+        test_perft("rbsgk/4p/5/P4/KGSBR b - 1", vec![1, 14, 181, 2512, 35401]);
+    }
+
+    #[test]
+    fn movegen_matches_brute_force() {
+        let cases = vec![
+            "lnsgkgsnl/1r5b1/ppppppppp/9/9/9/PPPPPPPPP/1B5R1/LNSGKGSNL b - 1",
+            "l6nl/5+P1gk/2np1S3/p1p4Pp/3P2Sp1/1PPb2P1P/P5GS1/R8/LN4bKL w GR5pnsg 1",
+            "8l/1l+R2P3/p2pBG1pp/kps1p4/Nn1P2G2/P1P1P2PP/1PS6/1KSG3+r1/LN2+p3L w Sbgn3p 124",
+            "9/9/9/3k5/9/5K3/9/9/9 b RB2G2S2N2L9Prb2g2s2n2l9p 1",
+            "9/9/7gp/7pk/9/7G1/9/PPPPPPPP1/K8 b P 1",
+            "9/9/7gp/1R5gk/9/7G1/9/PPPPPPPP1/K8 b P 1",
+            "lnsgkgsnl/1r5b1/pppp1pppp/9/4r4/9/PPPP1PPPP/1B5R1/LNSGKGSNL b - 1",
+            "4k4/9/4r4/9/4B4/9/4K4/9/9 b - 1",
+            "4k4/9/9/1b7/9/9/4K4/9/4r4 b G 1",
+            "4k4/4l4/9/9/4S4/9/4K4/9/9 b - 1",
+            "rbsgk/4p/5/P4/KGSBR b - 1",
+            "2k2/5/2P2/5/2K2 b RBGSPrbgs 1",
+            "rbnsgk/5p/6/6/P5/KGSNBR b - 1",
+            "5k/6/6/6/6/K5 b RBGSNPrbgsnp 1",
+        ];
+        for sfen in cases {
+            let position = Position::parse(sfen).unwrap();
+            assert_movegen_matches_brute_force(&position);
+            for &m in position.legal_moves().iter() {
+                assert_movegen_matches_brute_force(&position.do_move(m).unwrap());
+            }
+        }
+    }
+
+    #[test]
+    fn filtered_movegen() {
+        let cases = vec![
+            "lnsgkgsnl/1r5b1/ppppppppp/9/9/9/PPPPPPPPP/1B5R1/LNSGKGSNL b - 1",
+            "l6nl/5+P1gk/2np1S3/p1p4Pp/3P2Sp1/1PPb2P1P/P5GS1/R8/LN4bKL w GR5pnsg 1",
+            "8l/1l+R2P3/p2pBG1pp/kps1p4/Nn1P2G2/P1P1P2PP/1PS6/1KSG3+r1/LN2+p3L w Sbgn3p 124",
+            "9/9/7gp/1R5gk/9/7G1/9/PPPPPPPP1/K8 b P 1",
+            "4k4/9/4r4/9/4B4/9/4K4/9/9 b - 1",
+            "4k4/4l4/9/9/4S4/9/4K4/9/9 b - 1",
+        ];
+        for sfen in cases {
+            let position = Position::parse(sfen).unwrap();
+            let legal: Vec<Move> = position.legal_moves().into_iter().collect();
+            let filtered = |f: &dyn Fn(&Move) -> bool| -> Vec<Move> {
+                legal.iter().copied().filter(|m| f(m)).collect()
+            };
+
+            let pseudo_legal = position.pseudo_legal_moves();
+            assert!(legal.iter().all(|m| pseudo_legal.contains(m)), "{sfen}");
+            assert!(
+                pseudo_legal
+                    .iter()
+                    .all(|&m| legal.contains(&m) || !position.is_legal(m)),
+                "{sfen}"
+            );
+
+            let captures = filtered(&|&m| match m {
+                Move::Normal { to, .. } => !position.is_empty(to),
+                _ => false,
+            });
+            assert_eq!(captures, position.captures().to_vec(), "{sfen}");
+
+            let checks = filtered(&|&m| position.do_move(m).unwrap().is_in_check());
+            assert_eq!(checks, position.checks().to_vec(), "{sfen}");
+
+            let drops = filtered(&|m| matches!(m, Move::Drop(..)));
+            assert_eq!(drops, position.drops().to_vec(), "{sfen}");
+
+            let promotions = filtered(&|m| matches!(m, Move::Normal { promo: true, .. }));
+            assert_eq!(promotions, position.promotions().to_vec(), "{sfen}");
+        }
+    }
+
+    #[test]
+    fn incremental_key_matches_fresh_key() {
+        let cases = vec![
+            "lnsgkgsnl/1r5b1/ppppppppp/9/9/9/PPPPPPPPP/1B5R1/LNSGKGSNL b - 1",
+            "l6nl/5+P1gk/2np1S3/p1p4Pp/3P2Sp1/1PPb2P1P/P5GS1/R8/LN4bKL w GR5pnsg 1",
+            "8l/1l+R2P3/p2pBG1pp/kps1p4/Nn1P2G2/P1P1P2PP/1PS6/1KSG3+r1/LN2+p3L w Sbgn3p 124",
+        ];
+        for sfen in cases {
+            let position = Position::parse(sfen).unwrap();
+            for &m in position.legal_moves().iter() {
+                let child = position.do_move(m).unwrap();
+                let fresh = Position::parse(&child.to_string()).unwrap();
+                assert_eq!(child.key(), fresh.key(), "{position} {m}");
+                assert_ne!(child.key(), position.key());
+            }
+        }
+    }
+
+    #[test]
+    fn move_list_spills() {
+        // Far more pieces than a set, so more moves than any reachable position has
+        let sfen = "R8/2K1S1SSk/4B4/9/9/9/9/+R7+R/1L1L1L3 b RBGSNLP3g3n17p 1";
+        let position = Position::parse(sfen).unwrap();
+        let pseudo_legal = position.pseudo_legal_moves();
+        assert!(pseudo_legal.len() > 600, "{}", pseudo_legal.len());
+        let legal = position.legal_moves();
+        assert!(legal.iter().all(|m| pseudo_legal.contains(m)));
+        assert_eq!(legal.len(), legal.clone().into_iter().count());
+    }
+
+    #[test]
+    fn hand_keys_per_count() {
+        let mut keys = HashSet::new();
+        let mut positions = 0;
+        for (pt, max) in [
+            ('P', 18),
+            ('L', 4),
+            ('N', 4),
+            ('S', 4),
+            ('G', 4),
+            ('B', 2),
+            ('R', 2),
+        ] {
+            for (color, count) in iproduct!([pt, pt.to_ascii_lowercase()], 1..=max) {
+                let position =
+                    Position::parse(&format!("4k4/9/9/9/9/9/9/9/4K4 b {count}{color} 1"));
+                keys.insert(position.unwrap().key());
+                positions += 1;
+            }
+        }
+        keys.insert(
+            Position::parse("4k4/9/9/9/9/9/9/9/4K4 b - 1")
+                .unwrap()
+                .key(),
+        );
+        assert_eq!(positions + 1, keys.len());
+    }
+
+    #[test]
+    fn key_collision_is_not_repetition() {
+        let mut game = Game::new(Position::default());
+        let m = Move::parse("7g7f").unwrap();
+        // Pretend the position after 7g7f has the key of a different position seen 3 times
+        let key = Position::default().do_move(m).unwrap().key();
+        game.repetitions.insert(key, (3, 0));
+        assert_eq!(GameOutcome::Undetermined, game.do_move(m));
+    }
+
+    #[test]
+    fn test_outcome() {
+        let cases = vec![
+            (
+                "lnsgkgsnl/1r5b1/ppppppppp/9/9/9/PPPPPPPPP/1B5R1/LNSGKGSNL b - 1",
+                "2h7h 8b9b 7h6h 9b8b 6h7h 8b9b 7h6h 9b8b 6h7h 8b9b 7h6h 9b8b",
+                "6h7h",
+                GameOutcome::DrawBySennichite,
+            ),
+            (
+                "l7l/g1S6/k1n4pp/ppN3p2/2p4P1/P2P1bP2/KSN5P/1G7/L1bS4L w 2RSN5P2g2p 1",
+                "G*8i R*7h 8i8h 7h8h G*8i G*7h 8i8h 7h8h G*8i G*7h 8i8h 7h8h G*8i G*7h 8i8h 7h8h G*8i G*7h",
+                "8i8h",
+                GameOutcome::DrawBySennichite,
+            ),
+            (
+                "lnsG3Rl/1rg1ks3/p1ppppp1p/9/9/1p7/PPPPPPP1P/1B7/LNSGKGSNL b BNPp 1",
+                "6a5a 5b6b 5a6a 6b5b 6a5a 5b6b 5a6a 6b5b 6a5a 5b6b 5a6a",
+                "6b5b",
+                GameOutcome::LossByPerpetual(Color::Sente),
+            ),
+        ];
+        for (sfen, moves, last_move, expected_outcome) in cases {
+            println!("{sfen}");
+            let position = Position::parse(sfen).unwrap();
+            let mut game = Game::new(position);
+            for mstr in moves.split(' ') {
+                let m = Move::parse(mstr).unwrap();
+                let intermediate_outcome = game.do_move(m);
+                println!("{m}: {:?}", intermediate_outcome);
+                assert_eq!(intermediate_outcome, GameOutcome::Undetermined);
+            }
+            let m = Move::parse(last_move).unwrap();
+            let final_outcome = game.do_move(m);
+            println!("{m}: {:?}", final_outcome);
+            assert_eq!(final_outcome, expected_outcome);
+        }
+    }
+
+    #[test]
+    fn sennichite_rules() {
+        // Plays `moves` and returns the first determined outcome and the number of moves played
+        let play = |sfen: &str, moves: &str, repetitions: usize, perpetual: PerpetualRule| {
+            let rules = GameRules {
+                repetitions,
+                perpetual,
+                ..GameRules::default()
+            };
+            let mut game = Game::with_rules(Position::parse(sfen).unwrap(), rules);
+            moves
+                .split(' ')
+                .map(|mstr| game.do_move(Move::parse(mstr).unwrap()))
+                .enumerate()
+                .find(|(_, outcome)| outcome.is_determined())
+                .map(|(i, outcome)| (outcome, i + 1))
+        };
+
+        let sfen = "lnsgkgsnl/1r5b1/ppppppppp/9/9/9/PPPPPPPPP/1B5R1/LNSGKGSNL b - 1";
+        let moves = "2h7h 8b9b 7h6h 9b8b 6h7h 8b9b 7h6h 9b8b 6h7h 8b9b 7h6h 9b8b 6h7h";
+        let repetition = GameOutcome::DrawByRepetitionAdjudication;
+        assert_eq!(
+            Some((repetition, 5)),
+            play(sfen, moves, 2, PerpetualRule::Loss)
+        );
+        assert_eq!(
+            Some((repetition, 9)),
+            play(sfen, moves, 3, PerpetualRule::Loss)
+        );
+        let sennichite = GameOutcome::DrawBySennichite;
+        assert_eq!(
+            Some((sennichite, 13)),
+            play(sfen, moves, 4, PerpetualRule::Loss)
+        );
+
+        let sfen = "lnsG3Rl/1rg1ks3/p1ppppp1p/9/9/1p7/PPPPPPP1P/1B7/LNSGKGSNL b BNPp 1";
+        let moves = "6a5a 5b6b 5a6a 6b5b 6a5a 5b6b 5a6a 6b5b 6a5a 5b6b 5a6a 6b5b";
+        let loss = GameOutcome::LossByPerpetual(Color::Sente);
+        assert_eq!(Some((loss, 12)), play(sfen, moves, 4, PerpetualRule::Loss));
+        assert_eq!(Some((loss, 4)), play(sfen, moves, 2, PerpetualRule::Loss));
+        let draw = GameOutcome::DrawByPerpetual(Color::Sente);
+        assert_eq!(Some((draw, 12)), play(sfen, moves, 4, PerpetualRule::Draw));
+
+        // Sente's rook is the first to return, so Sente loses under the repetition loss rule
+        let sfen = "lnsgkgsnl/1r5b1/ppppppppp/9/9/9/PPPPPPPPP/1B5R1/LNSGKGSNL b - 1";
+        let moves = "2h7h 8b9b 7h6h 9b8b 6h7h 8b9b 7h6h 9b8b 6h7h 8b9b 7h6h 9b8b 6h7h";
+        let rules = GameRules {
+            repetition: Some(RepetitionRule::Loss),
+            ..GameRules::default()
+        };
+        let mut game = Game::with_rules(Position::parse(sfen).unwrap(), rules);
+        let outcomes: Vec<GameOutcome> = moves
+            .split(' ')
+            .map(|mstr| game.do_move(Move::parse(mstr).unwrap()))
+            .collect();
+        assert_eq!(GameOutcome::LossBySennichite(Color::Sente), outcomes[12]);
+        assert!(
+            outcomes[..12]
+                .iter()
+                .all(|outcome| !outcome.is_determined())
+        );
+    }
+
+    #[test]
+    fn mirror_and_flip() {
+        let position =
+            Position::parse("lnsgkgsnl/1r5b1/ppppppppp/9/9/2P6/PP1PPPPPP/1B5R1/LNSGKGSNL w - 2")
+                .unwrap();
+        assert_eq!(
+            "lnsgkgsnl/1b5r1/ppppppppp/9/9/6P2/PPPPPP1PP/1R5B1/LNSGKGSNL w - 2",
+            position.mirror().to_string()
+        );
+        assert_eq!(
+            "lnsgkgsnl/1r5b1/pppppp1pp/6p2/9/9/PPPPPPPPP/1B5R1/LNSGKGSNL b - 2",
+            position.flip_colors().to_string()
+        );
+
+        let position = Position::parse("4k2l1/9/9/9/9/9/9/9/4K4 b G2p 1").unwrap();
+        assert_eq!(
+            "4k4/9/9/9/9/9/9/9/1L2K4 w 2Pg 1",
+            position.flip_colors().to_string()
+        );
+        assert_eq!(position, position.flip_colors().flip_colors());
+        assert_eq!(position, position.mirror().mirror());
+
+        let minishogi = Variant::Minishogi.startpos();
+        assert_eq!("kgsbr/p4/5/4P/RBSGK b - 1", minishogi.mirror().to_string());
+        assert_eq!(
+            "rbsgk/4p/5/P4/KGSBR w - 1",
+            minishogi.flip_colors().to_string()
+        );
+    }
+
+    #[test]
+    fn game_from_usi() {
+        let rules = GameRules::default();
+        let startpos = "lnsgkgsnl/1r5b1/ppppppppp/9/9/9/PPPPPPPPP/1B5R1/LNSGKGSNL b - 1";
+
+        let game = Game::from_usi("startpos", Variant::Standard, rules).unwrap();
+        assert_eq!(format!("sfen {startpos}"), game.usi_string());
+
+        let usi = format!("sfen {startpos} moves 7g7f 3c3d 8h2b+");
+        let game = Game::from_usi(&format!("position {usi}"), Variant::Standard, rules).unwrap();
+        assert_eq!(usi, game.usi_string());
+        assert_eq!(
+            "lnsgkgsnl/1r5+B1/pppppp1pp/6p2/9/2P6/PP1PPPPPP/7R1/LNSGKGSNL w B 4",
+            game.position().to_string()
+        );
+        let game = Game::from_usi(
+            "sfen 4k4/9/9/9/9/9/9/9/4K4 w G 1 moves",
+            Variant::Standard,
+            rules,
+        )
+        .unwrap();
+        assert_eq!("sfen 4k4/9/9/9/9/9/9/9/4K4 w G 1", game.usi_string());
+
+        // The repetitions in the move list count towards sennichite
+        let moves = "2h7h 8b9b 7h6h 9b8b 6h7h 8b9b 7h6h 9b8b 6h7h 8b9b 7h6h 9b8b";
+        let mut game =
+            Game::from_usi(&format!("startpos moves {moves}"), Variant::Standard, rules).unwrap();
+        assert_eq!(
+            GameOutcome::DrawBySennichite,
+            game.do_move(Move::parse("6h7h").unwrap())
+        );
+
+        for invalid in [
+            "",
+            "sfen 4k4/9/9 b -",
+            "sfen R8/2K1S1SSk/4B4/9/9/9/9/+R7+R/1L1L1L3 b RBGSNLP3g3n17p 1",
+            "startpos 7g7f",
+            "startpos moves 7g7f 7g7f",
+            "startpos moves 7g7f resign",
+            &format!("startpos moves {moves} 6h7h 8b9b"),
+        ] {
+            assert!(
+                Game::from_usi(invalid, Variant::Standard, rules).is_err(),
+                "{invalid}"
+            );
+        }
+    }
+
+    #[test]
+    fn impasse_rules() {
+        let declare = |sfen: &str, impasse: ImpasseRule| {
+            let position = Position::parse(sfen).unwrap();
+            let rules = GameRules {
+                impasse,
+                ..GameRules::default()
+            };
+            Game::with_rules(position, rules).do_move(Move::Win)
+        };
+
+        // 10 pieces in the zone and 28 points, against 26 points in Gote's hand
+        let sfen = "+R+B+P+P+P+P+P+P+P/G3K4/9/9/9/9/9/9/k8 b R5Pb3g4s4n4l6p 1";
+        let win = GameOutcome::WinInImpasse(Color::Sente);
+        let illegal = GameOutcome::LossByIllegal(Color::Sente);
+        assert_eq!(win, declare(sfen, ImpasseRule::Points27));
+        assert_eq!(
+            GameOutcome::DrawByImpasse,
+            declare(sfen, ImpasseRule::Points24)
+        );
+        assert_eq!(illegal, declare(sfen, ImpasseRule::Try));
+        assert_eq!(illegal, declare(sfen, ImpasseRule::None));
+
+        // 27 points is not enough for Sente
+        let sfen = "+R+B+P+P+P+P+P+P+P/G3K4/9/9/9/9/9/9/k8 b R4P 1";
+        assert_eq!(illegal, declare(sfen, ImpasseRule::Points27));
+
+        // 31 points wins under the 24-point rule
+        let sfen = "+R+B+P+P+P+P+P+P+P/G3K4/9/9/9/9/9/9/k8 b R8P 1";
+        assert_eq!(win, declare(sfen, ImpasseRule::Points24));
+
+        // Pieces outside the zone count towards both the ten pieces and the points
+        let sfen = "+R+B+P+P+P+P+P+P+P/4K4/9/G8/9/9/9/9/k8 b R5P 1";
+        assert_eq!(win, declare(sfen, ImpasseRule::Points27));
+        let sfen = "+R+B+P+P+P+P+P+P+P/G3K4/9/9/9/9/P8/9/k8 b R4P 1";
+        assert_eq!(win, declare(sfen, ImpasseRule::Points27));
+
+        // The king is one of the ten pieces, but nine are not enough
+        let sfen = "+R+B+P+P+P+P+P+P1/4K4/9/9/9/9/9/9/k8 b R7P 1";
+        assert_eq!(illegal, declare(sfen, ImpasseRule::Points27));
+
+        // 24 to 30 points only draws if the opponent has 24 points as well, here 22
+        let sfen = "+R+B+P+P+P+P+P+P+P/G3K4/9/9/9/9/9/9/k8 b R5Pb3g4s4n4l2p 1";
+        assert_eq!(win, declare(sfen, ImpasseRule::Points24));
+
+        // Try rule: reaching the opponent king's starting square wins
+        let sfen = "9/4K4/9/9/9/9/9/9/8k b - 1";
+        assert_eq!(win, declare(sfen, ImpasseRule::Try));
+        assert_eq!(illegal, declare(sfen, ImpasseRule::Points27));
+        let position = Position::parse(sfen).unwrap();
+        let rules = GameRules {
+            impasse: ImpasseRule::Try,
+            ..GameRules::default()
+        };
+        let try_move = Move::parse("5b5a").unwrap();
+        assert_eq!(win, Game::with_rules(position, rules).do_move(try_move));
+        assert_eq!(
+            GameOutcome::Undetermined,
+            Game::new(position).do_move(try_move)
+        );
+    }
+
+    #[test]
+    fn small_board_variants() {
+        for variant in Variant::ALL {
+            let startpos = variant.startpos();
+            assert_eq!(Some(variant), Variant::parse(variant.name()));
+            assert_eq!(variant, startpos.variant());
+            assert_eq!(Some(startpos), Position::parse(&startpos.to_string()));
+        }
+
+        // Pieces that are not part of the variant
+        assert_eq!(None, Position::parse("rbsgk/4p/5/P4/KGSBL b - 1"));
+        assert_eq!(None, Position::parse("rbsgk/4p/5/P4/KGSBR b N 1"));
+        assert_eq!(None, Position::parse("rbnsgk/5p/6/6/P5/KGSNBR b l 1"));
+
+        // Promotion zone and dead squares follow the board size
+        let position = Position::parse("2k2/5/P4/5/2K2 b S 1").unwrap();
+        assert!(position.is_legal(Move::parse("5c5b").unwrap()));
+        assert!(!position.is_legal(Move::parse("5c5b+").unwrap()));
+        assert!(!position.is_legal(Move::parse("S*6a").unwrap()));
+        assert!(!position.is_legal(Move::parse("S*1f").unwrap()));
+        let position = position.do_move(Move::parse("5c5b").unwrap()).unwrap();
+        let position = position.do_move(Move::parse("3a2a").unwrap()).unwrap();
+        assert!(position.is_legal(Move::parse("5b5a+").unwrap()));
+        assert!(!position.is_legal(Move::parse("5b5a").unwrap()));
+
+        // Repetition in minishogi is a loss for Sente unless a repetition rule is given
+        let repeat = |repetition: Option<RepetitionRule>| {
+            let rules = GameRules {
+                repetition,
+                ..GameRules::default()
+            };
+            let mut game = Game::with_rules(Variant::Minishogi.startpos(), rules);
+            ["5e4d", "1a2b", "4d5e", "2b1a"]
+                .iter()
+                .cycle()
+                .take(12)
+                .map(|m| game.do_move(Move::parse(m).unwrap()))
+                .last()
+                .unwrap()
+        };
+        assert_eq!(GameOutcome::LossBySennichite(Color::Sente), repeat(None));
+        assert_eq!(
+            GameOutcome::DrawBySennichite,
+            repeat(Some(RepetitionRule::Draw))
+        );
+        assert_eq!(
+            GameOutcome::LossBySennichite(Color::Gote),
+            repeat(Some(RepetitionRule::Loss))
+        );
+    }
+}
@@ -0,0 +1,427 @@
+use super::bitboard::{self, Bitboard};
+use super::{Color, HAND_PTYPES, Move, PieceType, Position, Square};
+use std::ops;
+
+// The maximum number of legal moves in any reachable shogi position is 593. Positions parsed from
+// an arbitrary SFEN can have more, and those moves spill over to the heap.
+const MAX_MOVES: usize = 600;
+
+#[derive(Clone, Debug)]
+pub struct MoveList {
+    moves: [Move; MAX_MOVES],
+    len: usize,
+    // Holds every move instead of `moves` once there are more than MAX_MOVES
+    spilled: Vec<Move>,
+}
+
+impl Default for MoveList {
+    fn default() -> Self {
+        MoveList {
+            moves: [Move::None; MAX_MOVES],
+            len: 0,
+            spilled: Vec::new(),
+        }
+    }
+}
+
+impl MoveList {
+    fn push(&mut self, m: Move) {
+        if self.len < MAX_MOVES && self.spilled.is_empty() {
+            self.moves[self.len] = m;
+        } else {
+            if self.spilled.is_empty() {
+                self.spilled.extend_from_slice(&self.moves[..self.len]);
+            }
+            self.spilled.push(m);
+        }
+        self.len += 1;
+    }
+
+    fn retain<F>(&mut self, mut f: F)
+    where
+        F: FnMut(Move) -> bool,
+    {
+        if !self.spilled.is_empty() {
+            self.spilled.retain(|&m| f(m));
+            self.len = self.spilled.len();
+            return;
+        }
+        let mut kept = 0;
+        for i in 0..self.len {
+            if f(self.moves[i]) {
+                self.moves[kept] = self.moves[i];
+                kept += 1;
+            }
+        }
+        self.len = kept;
+    }
+}
+
+/// Iterates over the moves of a `MoveList` by value, without allocating.
+#[derive(Clone, Debug)]
+pub struct MoveListIter {
+    list: MoveList,
+    index: usize,
+}
+
+impl Iterator for MoveListIter {
+    type Item = Move;
+
+    fn next(&mut self) -> Option<Move> {
+        let m = self.list.get(self.index).copied();
+        self.index += 1;
+        m
+    }
+
+    fn size_hint(&self) -> (usize, Option<usize>) {
+        let remaining = self.list.len.saturating_sub(self.index);
+        (remaining, Some(remaining))
+    }
+}
+
+impl ExactSizeIterator for MoveListIter {}
+
+impl IntoIterator for MoveList {
+    type Item = Move;
+    type IntoIter = MoveListIter;
+
+    fn into_iter(self) -> MoveListIter {
+        MoveListIter {
+            list: self,
+            index: 0,
+        }
+    }
+}
+
+impl<'a> IntoIterator for &'a MoveList {
+    type Item = &'a Move;
+    type IntoIter = std::slice::Iter<'a, Move>;
+
+    fn into_iter(self) -> Self::IntoIter {
+        self.iter()
+    }
+}
+
+impl ops::Deref for MoveList {
+    type Target = [Move];
+
+    fn deref(&self) -> &[Move] {
+        if self.spilled.is_empty() {
+            &self.moves[..self.len]
+        } else {
+            &self.spilled
+        }
+    }
+}
+
+impl Position {
+    pub fn occupied(&self) -> Bitboard {
+        self.color_bb[0] | self.color_bb[1]
+    }
+
+    pub fn pieces(&self, color: Color, pt: PieceType) -> Bitboard {
+        self.color_bb[color.to_index()] & self.piece_bb[pt as usize]
+    }
+
+    fn golds(&self) -> Bitboard {
+        self.piece_bb[PieceType::Gold as usize]
+            | self.piece_bb[PieceType::Tokin as usize]
+            | self.piece_bb[PieceType::NariLance as usize]
+            | self.piece_bb[PieceType::NariKnight as usize]
+            | self.piece_bb[PieceType::NariSilver as usize]
+    }
+
+    pub(super) fn king_square(&self, color: Color) -> Option<Square> {
+        self.pieces(color, PieceType::King).lsb()
+    }
+
+    /// Pieces of colour `color` that attack `sq`, given the board occupancy `occupied`.
+    pub fn attackers_to(&self, sq: Square, color: Color, occupied: Bitboard) -> Bitboard {
+        let bb = |pt: PieceType| self.piece_bb[pt as usize];
+        // Attack patterns are point-symmetric between colours, so we look outwards from `sq`
+        // as if we were a piece of the opposite colour.
+        let them = !color;
+        let attackers = (bitboard::attacks(them, PieceType::Pawn, sq, occupied)
+            & bb(PieceType::Pawn))
+            | (bitboard::attacks(them, PieceType::Knight, sq, occupied) & bb(PieceType::Knight))
+            | (bitboard::attacks(them, PieceType::Silver, sq, occupied) & bb(PieceType::Silver))
+            | (bitboard::attacks(them, PieceType::Gold, sq, occupied) & self.golds())
+            | (bitboard::attacks(them, PieceType::King, sq, occupied)
+                & (bb(PieceType::King) | bb(PieceType::Horse) | bb(PieceType::Dragon)))
+            | (bitboard::lance_attacks(them, sq, occupied) & bb(PieceType::Lance))
+            | (bitboard::bishop_attacks(sq, occupied)
+                & (bb(PieceType::Bishop) | bb(PieceType::Horse)))
+            | (bitboard::rook_attacks(sq, occupied)
+                & (bb(PieceType::Rook) | bb(PieceType::Dragon)));
+        attackers & self.color_bb[color.to_index()]
+    }
+
+    /// Enemy pieces giving check to the side to move.
+    pub fn checkers(&self) -> Bitboard {
+        match self.king_square(self.stm) {
+            Some(king_sq) => self.attackers_to(king_sq, !self.stm, self.occupied()),
+            None => Bitboard::EMPTY,
+        }
+    }
+
+    // Pieces of the side to move that are pinned to their own king.
+    fn pinned(&self, king_sq: Square) -> Bitboard {
+        let us = self.stm;
+        let them = !us;
+        let snipers = (self.pieces(them, PieceType::Lance)
+            & bitboard::lance_attacks(us, king_sq, Bitboard::EMPTY))
+            | ((self.pieces(them, PieceType::Bishop) | self.pieces(them, PieceType::Horse))
+                & bitboard::bishop_attacks(king_sq, Bitboard::EMPTY))
+            | ((self.pieces(them, PieceType::Rook) | self.pieces(them, PieceType::Dragon))
+                & bitboard::rook_attacks(king_sq, Bitboard::EMPTY));
+
+        let occupied = self.occupied();
+        let mut pinned = Bitboard::EMPTY;
+        for sniper in snipers {
+            let blockers = bitboard::between(king_sq, sniper) & occupied;
+            if blockers.count() == 1 {
+                pinned |= blockers & self.color_bb[us.to_index()];
+            }
+        }
+        pinned
+    }
+
+    /// Pieces of the side to move of type `pt`, other than the one on `from`, that could also
+    /// legally move to `to`. Used to disambiguate moves in human-readable notations.
+    pub(super) fn other_movers(&self, pt: PieceType, from: Option<Square>, to: Square) -> Bitboard {
+        let mut movers =
+            self.attackers_to(to, self.stm, self.occupied()) & self.pieces(self.stm, pt);
+        if let Some(from) = from {
+            movers &= !Bitboard::from_square(from);
+        }
+        for sq in movers {
+            let legal = [false, true].iter().any(|&promo| {
+                self.is_legal(Move::Normal {
+                    from: sq,
+                    to,
+                    promo,
+                })
+            });
+            if !legal {
+                movers &= !Bitboard::from_square(sq);
+            }
+        }
+        movers
+    }
+
+    fn push_board_moves(&self, list: &mut MoveList, pt: PieceType, from: Square, to: Square) {
+        let us = self.stm;
+        if !self.is_ikidokorononai(us, pt, to) {
+            list.push(Move::Normal {
+                from,
+                to,
+                promo: false,
+            });
+        }
+        if pt.promotable() && (self.is_promo_square(from, us) || self.is_promo_square(to, us)) {
+            list.push(Move::Normal {
+                from,
+                to,
+                promo: true,
+            });
+        }
+    }
+
+    // Drops onto `targets`, obeying the dead square and 二歩 rules. 打ち歩詰め is only
+    // checked when `legal` is set.
+    fn generate_drops(&self, list: &mut MoveList, targets: Bitboard, legal: bool) {
+        let us = self.stm;
+        let hand = &self.hand[us.to_index()];
+        let their_king = self.king_square(!us);
+
+        for pt in HAND_PTYPES {
+            if hand.get(pt) == 0 {
+                continue;
+            }
+
+            let mut targets = targets;
+            let dead_ranks = match pt {
+                PieceType::Pawn | PieceType::Lance => 1,
+                PieceType::Knight => 2,
+                _ => 0,
+            };
+            for rank in 0..dead_ranks {
+                let rank = self.relative_rank(Square::new(0, rank).unwrap(), us);
+                targets &= !Bitboard::rank(rank);
+            }
+
+            if pt == PieceType::Pawn {
+                // Illegal move rule: 二歩
+                for pawn in self.pieces(us, PieceType::Pawn) {
+                    targets &= !Bitboard::file(pawn.file());
+                }
+            }
+
+            for to in targets {
+                let m = Move::Drop(pt, to);
+                // Illegal move rule: 打ち歩詰め
+                if legal
+                    && pt == PieceType::Pawn
+                    && let Some(their_king) = their_king
+                    && bitboard::attacks(us, PieceType::Pawn, to, Bitboard::EMPTY)
+                        .contains(their_king)
+                    && !self.is_legal(m)
+                {
+                    continue;
+                }
+                list.push(m);
+            }
+        }
+    }
+
+    fn generate_legal(&self, list: &mut MoveList) {
+        let us = self.stm;
+        let them = !us;
+        let ours = self.color_bb[us.to_index()];
+        let occupied = self.occupied();
+        let board = self.variant.board_mask();
+        let king_sq = self.king_square(us);
+        let checkers = self.checkers();
+
+        if let Some(king_sq) = king_sq {
+            let without_king = occupied ^ Bitboard::from_square(king_sq);
+            let targets = bitboard::attacks(us, PieceType::King, king_sq, occupied) & !ours & board;
+            for to in targets {
+                if self.attackers_to(to, them, without_king).is_empty() {
+                    list.push(Move::Normal {
+                        from: king_sq,
+                        to,
+                        promo: false,
+                    });
+                }
+            }
+        }
+
+        if checkers.count() > 1 {
+            return;
+        }
+
+        let (move_targets, drop_targets) = match (king_sq, checkers.lsb()) {
+            (Some(king_sq), Some(checker)) => {
+                let block = bitboard::between(king_sq, checker);
+                (block | Bitboard::from_square(checker), block)
+            }
+            _ => (!ours & board, !occupied & board),
+        };
+        let pinned = match king_sq {
+            Some(king_sq) => self.pinned(king_sq),
+            None => Bitboard::EMPTY,
+        };
+
+        for from in ours & !self.piece_bb[PieceType::King as usize] {
+            let pt = self.board[from.to_index()].1;
+            let mut targets = bitboard::attacks(us, pt, from, occupied) & move_targets;
+            if pinned.contains(from)
+                && let Some(king_sq) = king_sq
+            {
+                targets &= bitboard::line(king_sq, from);
+            }
+            for to in targets {
+                self.push_board_moves(list, pt, from, to);
+            }
+        }
+
+        self.generate_drops(list, drop_targets, true);
+    }
+
+    fn generate_pseudo_legal(&self, list: &mut MoveList) {
+        let us = self.stm;
+        let ours = self.color_bb[us.to_index()];
+        let occupied = self.occupied();
+        let board = self.variant.board_mask();
+
+        for from in ours {
+            let pt = self.board[from.to_index()].1;
+            for to in bitboard::attacks(us, pt, from, occupied) & !ours & board {
+                self.push_board_moves(list, pt, from, to);
+            }
+        }
+
+        self.generate_drops(list, !occupied & board, false);
+    }
+
+    /// Whether `m`, assumed to be legal, gives check to the opponent.
+    pub fn gives_check(&self, m: Move) -> bool {
+        let us = self.stm;
+        let Some(their_king) = self.king_square(!us) else {
+            return false;
+        };
+        let (from, to, pt) = match m {
+            Move::Drop(pt, to) => (None, to, pt),
+            Move::Normal { from, to, promo } => {
+                let pt = self.board[from.to_index()].1;
+                (Some(from), to, if promo { pt.promote() } else { pt })
+            }
+            Move::None | Move::Win | Move::Resign => return false,
+        };
+
+        let mut occupied = self.occupied() | Bitboard::from_square(to);
+        if let Some(from) = from {
+            occupied &= !Bitboard::from_square(from);
+        }
+        if bitboard::attacks(us, pt, to, occupied).contains(their_king) {
+            return true;
+        }
+
+        // Discovered check: any other of our pieces attacking the king after the move
+        match from {
+            Some(from) => !(self.attackers_to(their_king, us, occupied)
+                & !Bitboard::from_square(from))
+            .is_empty(),
+            None => false,
+        }
+    }
+
+    pub fn has_legal_move(&self) -> bool {
+        !self.legal_moves().is_empty()
+    }
+
+    /// All legal moves.
+    pub fn legal_moves(&self) -> MoveList {
+        let mut list = MoveList::default();
+        self.generate_legal(&mut list);
+        list
+    }
+
+    /// Moves that follow the movement rules of each piece, including dead squares and 二歩 for
+    /// drops, without checking whether they leave the king in check or are 打ち歩詰め.
+    pub fn pseudo_legal_moves(&self) -> MoveList {
+        let mut list = MoveList::default();
+        self.generate_pseudo_legal(&mut list);
+        list
+    }
+
+    /// Legal moves that capture a piece.
+    pub fn captures(&self) -> MoveList {
+        let theirs = self.color_bb[(!self.stm).to_index()];
+        let mut list = self.legal_moves();
+        list.retain(|m| matches!(m, Move::Normal { to, .. } if theirs.contains(to)));
+        list
+    }
+
+    /// Legal moves that give check.
+    pub fn checks(&self) -> MoveList {
+        let mut list = self.legal_moves();
+        list.retain(|m| self.gives_check(m));
+        list
+    }
+
+    /// Legal drops.
+    pub fn drops(&self) -> MoveList {
+        let mut list = self.legal_moves();
+        list.retain(|m| matches!(m, Move::Drop(..)));
+        list
+    }
+
+    /// Legal moves that promote a piece.
+    pub fn promotions(&self) -> MoveList {
+        let mut list = self.legal_moves();
+        list.retain(|m| matches!(m, Move::Normal { promo: true, .. }));
+        list
+    }
+}
@@ -0,0 +1,134 @@
+use super::{Move, Position};
+use std::collections::HashMap;
+
+// Depth-limited AND/OR search for a forced mate (詰み) by the side to move. The attacker may
+// only play checks; the defender may play any legal move.
+struct MateSearch {
+    nodes: u64,
+    max_nodes: u64,
+    aborted: bool,
+    // Zobrist key -> largest number of plies for which the attacker was shown to have no mate
+    disproved: HashMap<u64, usize>,
+}
+
+impl MateSearch {
+    fn visit(&mut self) -> bool {
+        self.nodes += 1;
+        if self.nodes > self.max_nodes {
+            self.aborted = true;
+        }
+        !self.aborted
+    }
+
+    // OR node: the attacker is to move and needs a mate within `plies` plies.
+    fn attack(&mut self, position: &Position, plies: usize) -> Option<Vec<Move>> {
+        if plies == 0 {
+            return None;
+        }
+        if self
+            .disproved
+            .get(&position.key())
+            .is_some_and(|&disproved| disproved >= plies)
+        {
+            return None;
+        }
+
+        for &m in position.checks().iter() {
+            if !self.visit() {
+                return None;
+            }
+            let child = position.do_move(m).unwrap();
+            if let Some(mut line) = self.defend(&child, plies - 1) {
+                line.insert(0, m);
+                return Some(line);
+            }
+        }
+
+        if !self.aborted {
+            self.disproved.insert(position.key(), plies);
+        }
+        None
+    }
+
+    // AND node: the defender is in check and every reply has to be mated within `plies` plies.
+    // Returns the longest defence.
+    fn defend(&mut self, position: &Position, plies: usize) -> Option<Vec<Move>> {
+        let evasions = position.legal_moves();
+        if evasions.is_empty() {
+            return Some(vec![]);
+        }
+        if plies < 2 {
+            return None;
+        }
+
+        let mut longest: Vec<Move> = vec![];
+        for &m in evasions.iter() {
+            if !self.visit() {
+                return None;
+            }
+            let child = position.do_move(m).unwrap();
+            let line = self.attack(&child, plies - 1)?;
+            if line.len() + 1 > longest.len() {
+                longest = std::iter::once(m).chain(line).collect();
+            }
+        }
+        Some(longest)
+    }
+}
+
+impl Position {
+    /// Searches for a forced mate by the side to move in at most `max_plies` plies, giving up
+    /// after visiting `max_nodes` positions. Returns the mating line, with the longest defence,
+    /// if a mate was proven.
+    pub fn find_mate(&self, max_plies: usize, max_nodes: u64) -> Option<Vec<Move>> {
+        let mut search = MateSearch {
+            nodes: 0,
+            max_nodes,
+            aborted: false,
+            disproved: HashMap::new(),
+        };
+        for plies in (1..=max_plies).step_by(2) {
+            if let Some(line) = search.attack(self, plies) {
+                return Some(line);
+            }
+            if search.aborted {
+                return None;
+            }
+        }
+        None
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn mate_length(sfen: &str, max_plies: usize) -> Option<usize> {
+        let position = Position::parse(sfen).unwrap();
+        position
+            .find_mate(max_plies, 1_000_000)
+            .map(|line| line.len())
+    }
+
+    #[test]
+    fn find_mate() {
+        // 頭金
+        assert_eq!(Some(1), mate_length("4k4/9/4P4/9/9/9/9/9/4K4 b G 1", 1));
+        assert_eq!(Some(1), mate_length("4k4/9/4P4/9/9/9/9/9/4K4 b G 1", 9));
+
+        // R*1c 2a, 1c2c+ 1a, G*2a
+        let sfen = "8k/9/9/9/9/9/9/9/4K4 b R2G 1";
+        assert_eq!(None, mate_length(sfen, 3));
+        assert_eq!(Some(5), mate_length(sfen, 5));
+
+        // A single gold is never enough
+        assert_eq!(None, mate_length("4k4/9/9/9/9/9/9/9/4K4 b G 1", 5));
+
+        // Dropping a pawn to mate is illegal
+        assert_eq!(None, mate_length("kn7/9/1G7/9/9/9/9/9/4K4 b P 1", 3));
+
+        // Node budget exhausted
+        let position = Position::parse(sfen).unwrap();
+        assert_eq!(None, position.find_mate(5, 100));
+    }
+}
@@ -0,0 +1,153 @@
+use super::{Color, HAND_PTYPES, PieceType, Place, Position, Square};
+use std::fmt;
+
+/// A way in which a position breaks the rules of shogi, as found by `Position::validate`.
+#[derive(Copy, Clone, PartialEq, Eq, Debug)]
+pub enum Violation {
+    /// Two unpromoted pawns of the same colour on one file (二歩).
+    Nifu(Color, i8),
+    /// A piece on a square from which it can never move (行き所のない駒).
+    DeadPiece(Place, Square),
+    /// More pieces of a type on the board and in hand than the variant has.
+    TooManyPieces(PieceType, usize),
+    /// A side without exactly one king. Holds the number of kings found.
+    KingCount(Color, usize),
+    /// The side that is not to move is in check.
+    OpponentInCheck(Color),
+}
+
+fn color_name(color: Color) -> &'static str {
+    match color {
+        Color::Sente => "Sente",
+        Color::Gote => "Gote",
+    }
+}
+
+impl fmt::Display for Violation {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        match *self {
+            Violation::Nifu(color, file) => {
+                write!(
+                    f,
+                    "{} has two pawns on file {}",
+                    color_name(color),
+                    file + 1
+                )
+            }
+            Violation::DeadPiece(place, sq) => write!(f, "{place} on {sq} can never move"),
+            Violation::TooManyPieces(pt, count) => write!(f, "{count} pieces of type {pt}"),
+            Violation::KingCount(color, count) => {
+                write!(f, "{} has {count} kings", color_name(color))
+            }
+            Violation::OpponentInCheck(color) => {
+                write!(f, "{} is in check but not to move", color_name(color))
+            }
+        }
+    }
+}
+
+impl Position {
+    fn piece_count(&self, pt: PieceType) -> usize {
+        let on_board = self
+            .board
+            .iter()
+            .filter(|place| !place.is_empty() && place.1.demote() == pt)
+            .count();
+        let in_hand = match pt {
+            PieceType::King => 0,
+            _ => self.hand.iter().map(|hand| hand.get(pt) as usize).sum(),
+        };
+        on_board + in_hand
+    }
+
+    /// Rule violations that make this position impossible to reach in a game. An empty list
+    /// means the position is valid.
+    pub fn validate(&self) -> Vec<Violation> {
+        let mut violations = vec![];
+        let size = self.variant.size();
+
+        for color in [Color::Sente, Color::Gote] {
+            for file in 0..size {
+                let pawns = (0..size)
+                    .filter(|&rank| {
+                        self.board[Square::new(file, rank).unwrap().to_index()]
+                            == Place(color, PieceType::Pawn)
+                    })
+                    .count();
+                if pawns > 1 {
+                    violations.push(Violation::Nifu(color, file));
+                }
+            }
+        }
+
+        for (i, &place) in self.board.iter().enumerate() {
+            let sq = Square(i as u8);
+            if !place.is_empty() && self.is_ikidokorononai(place.0, place.1, sq) {
+                violations.push(Violation::DeadPiece(place, sq));
+            }
+        }
+
+        let startpos = self.variant.startpos();
+        for pt in HAND_PTYPES {
+            let count = self.piece_count(pt);
+            if count > startpos.piece_count(pt) {
+                violations.push(Violation::TooManyPieces(pt, count));
+            }
+        }
+
+        for color in [Color::Sente, Color::Gote] {
+            let kings = self.pieces(color, PieceType::King).count() as usize;
+            if kings != 1 {
+                violations.push(Violation::KingCount(color, kings));
+            }
+        }
+
+        let them = !self.stm;
+        if let Some(king_sq) = self.king_square(them)
+            && !self
+                .attackers_to(king_sq, self.stm, self.occupied())
+                .is_empty()
+        {
+            violations.push(Violation::OpponentInCheck(them));
+        }
+
+        violations
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn violations(sfen: &str) -> Vec<String> {
+        let position = Position::parse(sfen).unwrap();
+        position.validate().iter().map(|v| v.to_string()).collect()
+    }
+
+    #[test]
+    fn validate() {
+        assert!(Position::default().validate().is_empty());
+        assert!(violations("rbsgk/4p/5/P4/KGSBR b - 1").is_empty());
+
+        assert_eq!(
+            vec!["Sente has two pawns on file 5"],
+            violations("4k4/9/9/9/4P4/9/4P4/9/4K4 b - 1")
+        );
+        assert_eq!(
+            vec!["N on 1b can never move", "l on 9i can never move"],
+            violations("4k4/8N/9/9/9/9/9/9/l3K4 b - 1")
+        );
+        assert_eq!(
+            vec!["19 pieces of type P"],
+            violations("4k4/9/9/9/9/9/9/9/4K4 b 10P9p 1")
+        );
+        assert_eq!(
+            vec!["Sente has 0 kings", "Gote has 2 kings"],
+            violations("3kk4/9/9/9/9/9/9/9/9 b - 1")
+        );
+        assert_eq!(
+            vec!["Gote is in check but not to move"],
+            violations("4k4/4R4/9/9/9/9/9/9/4K4 b - 1")
+        );
+    }
+}
@@ -0,0 +1,84 @@
+use super::bitboard::Bitboard;
+use super::{PieceType, Position};
+
+/// Board variants. Small boards are stored in the corner of the 9x9 board that holds files 1 to
+/// N and ranks a to N, so squares keep their USI names (e.g. minishogi runs from 1a to 5e).
+#[derive(Copy, Clone, PartialEq, Eq, Debug, Default)]
+pub enum Variant {
+    #[default]
+    Standard,
+    /// 5x5 minishogi (5五将棋). Repetition is a loss for Sente.
+    Minishogi,
+    /// 6x6 Judkins shogi.
+    Judkins,
+}
+
+impl Variant {
+    pub const ALL: [Variant; 3] = [Variant::Standard, Variant::Minishogi, Variant::Judkins];
+
+    pub fn parse(s: &str) -> Option<Variant> {
+        Variant::ALL.into_iter().find(|v| v.name() == s)
+    }
+
+    /// Name used on the command line, e.g. "minishogi".
+    pub fn name(self) -> &'static str {
+        match self {
+            Variant::Standard => "standard",
+            Variant::Minishogi => "minishogi",
+            Variant::Judkins => "judkins",
+        }
+    }
+
+    /// Value of the `USI_Variant` option that selects this variant in engines. Engines play
+    /// standard shogi unless told otherwise, so there is none for it.
+    pub fn usi_variant(self) -> Option<&'static str> {
+        match self {
+            Variant::Standard => None,
+            Variant::Minishogi => Some("minishogi"),
+            Variant::Judkins => Some("judkins"),
+        }
+    }
+
+    /// Number of files and ranks.
+    pub fn size(self) -> i8 {
+        match self {
+            Variant::Standard => 9,
+            Variant::Minishogi => 5,
+            Variant::Judkins => 6,
+        }
+    }
+
+    /// Depth of the promotion zone in ranks.
+    pub fn promotion_ranks(self) -> i8 {
+        match self {
+            Variant::Standard => 3,
+            Variant::Minishogi => 1,
+            Variant::Judkins => 2,
+        }
+    }
+
+    pub(super) fn from_size(size: i8) -> Option<Variant> {
+        Variant::ALL.into_iter().find(|v| v.size() == size)
+    }
+
+    /// Whether pieces of type `pt` (promoted or not) take part in this variant.
+    pub fn has_piece(self, pt: PieceType) -> bool {
+        !matches!(
+            (self, pt.demote()),
+            (Variant::Minishogi, PieceType::Lance | PieceType::Knight)
+                | (Variant::Judkins, PieceType::Lance)
+        )
+    }
+
+    pub(super) fn board_mask(self) -> Bitboard {
+        Bitboard::board(self.size())
+    }
+
+    pub fn startpos(self) -> Position {
+        match self {
+            Variant::Standard => Position::default(),
+            Variant::Minishogi => Position::parse("rbsgk/4p/5/P4/KGSBR b - 1").unwrap(),
+            Variant::Judkins => Position::parse("rbnsgk/5p/6/6/P5/KGSNBR b - 1").unwrap(),
+        }
+    }
+}
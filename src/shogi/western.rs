@@ -0,0 +1,63 @@
+use super::{Color, Move, Position};
+
+impl Position {
+    /// Western (Hodges) move text, e.g. "P-7f", "Bx2b+", "S*5e" or "N-3c=". The origin square is
+    /// only given when another piece of the same kind could also move to the destination, as in
+    /// "G6i-5h".
+    pub fn format_western_move(&self, m: Move) -> String {
+        match m {
+            Move::Drop(pt, to) => format!("{}*{to}", pt.to_str(Color::Sente)),
+            Move::Normal { from, to, promo } => {
+                let Some((color, pt)) = self.piece_at(from) else {
+                    return String::new();
+                };
+                let origin = if self.other_movers(pt, Some(from), to).is_empty() {
+                    String::new()
+                } else {
+                    from.to_string()
+                };
+                let action = if self.is_empty(to) { "-" } else { "x" };
+                let promotion = if promo {
+                    "+"
+                } else if pt.promotable()
+                    && (self.is_promo_square(from, color) || self.is_promo_square(to, color))
+                {
+                    "="
+                } else {
+                    ""
+                };
+                format!("{}{origin}{action}{to}{promotion}", pt.to_str(Color::Sente))
+            }
+            Move::None | Move::Win | Move::Resign => m.to_string(),
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn western(sfen: &str, m: &str) -> String {
+        let position = Position::parse(sfen).unwrap();
+        position.format_western_move(Move::parse(m).unwrap())
+    }
+
+    #[test]
+    fn western_move_text() {
+        let startpos = "lnsgkgsnl/1r5b1/ppppppppp/9/9/9/PPPPPPPPP/1B5R1/LNSGKGSNL b - 1";
+        assert_eq!("P-7f", western(startpos, "7g7f"));
+        assert_eq!("G6i-5h", western(startpos, "6i5h"));
+        assert_eq!("G4i-5h", western(startpos, "4i5h"));
+        assert_eq!("G-7h", western(startpos, "6i7h"));
+
+        let sfen = "lnsgkgsnl/1r5b1/pppppp1pp/6p2/9/2P6/PP1PPPPPP/1B5R1/LNSGKGSNL b - 3";
+        assert_eq!("Bx2b+", western(sfen, "8h2b+"));
+        assert_eq!("Bx2b=", western(sfen, "8h2b"));
+
+        let sfen = "lnsgkg1nl/1r5s1/pppppp1pp/6p2/9/2P6/PP1PPPPPP/7R1/LNSGKGSNL b Bb 5";
+        assert_eq!("B*4e", western(sfen, "B*4e"));
+
+        assert_eq!("N-4c=", western("4k4/9/9/9/4N4/9/9/9/4K4 b - 1", "5e4c"));
+        assert_eq!("+R-5e", western("4k4/9/9/9/9/9/9/4+R4/4K4 b - 1", "5h5e"));
+    }
+}
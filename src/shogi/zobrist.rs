@@ -0,0 +1,51 @@
+use super::{Color, PieceType, Place, Square};
+use lazy_static::lazy_static;
+use rand::{Rng, SeedableRng};
+
+// A hand never holds more than the 18 pawns of a set
+const MAX_HAND_COUNT: usize = 18;
+
+struct Keys {
+    board: [[[u64; 81]; 16]; 2],
+    hand: [[[u64; MAX_HAND_COUNT + 1]; 16]; 2],
+    side: u64,
+}
+
+lazy_static! {
+    static ref KEYS: Keys = {
+        // Fixed seed, so that keys are stable across runs
+        let mut rng = rand_chacha::ChaCha8Rng::seed_from_u64(0x5348_4f47_4954_4553);
+        let mut keys = Keys {
+            board: [[[0; 81]; 16]; 2],
+            hand: [[[0; MAX_HAND_COUNT + 1]; 16]; 2],
+            side: rng.random(),
+        };
+        for key in keys.board.iter_mut().flatten().flatten() {
+            *key = rng.random();
+        }
+        // An empty hand contributes nothing
+        for key in keys.hand.iter_mut().flatten().flat_map(|keys| &mut keys[1..]) {
+            *key = rng.random();
+        }
+        keys
+    };
+}
+
+pub fn place_key(sq: Square, place: Place) -> u64 {
+    if place.is_empty() {
+        0
+    } else {
+        KEYS.board[place.0.to_index()][place.1 as usize][sq.to_index()]
+    }
+}
+
+// Each count has its own key, so a change of count is XORed out and in like a piece on the board.
+// Larger counts only occur in positions with more pieces than a set and share the last key.
+pub fn hand_key(color: Color, pt: PieceType, count: u8) -> u64 {
+    KEYS.hand[color.to_index()][pt as usize][(count as usize).min(MAX_HAND_COUNT)]
+}
+
+// Toggled whenever the side to move changes; Sente to move contributes nothing.
+pub fn side_key() -> u64 {
+    KEYS.side
+}
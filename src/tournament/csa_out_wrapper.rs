@@ -0,0 +1,50 @@
+use crate::{
+    cli, csa,
+    tournament::{MatchResult, MatchTicket, Tournament, TournamentState},
+};
+
+pub struct CsaOutWrapper {
+    inner: Box<dyn Tournament>,
+    csa: csa::CsaWriter,
+}
+
+impl CsaOutWrapper {
+    pub fn new(
+        inner: Box<dyn Tournament>,
+        options: &cli::CsaOutOptions,
+        meta: &cli::MetaDataOptions,
+        engine_names: Vec<String>,
+    ) -> Result<CsaOutWrapper, std::io::Error> {
+        Ok(CsaOutWrapper {
+            inner,
+            csa: csa::CsaWriter::new(options, meta, engine_names)?,
+        })
+    }
+}
+
+impl Tournament for CsaOutWrapper {
+    fn next(&mut self) -> Option<MatchTicket> {
+        self.inner.as_mut().next()
+    }
+    fn match_started(&mut self, ticket: MatchTicket) {
+        self.inner.as_mut().match_started(ticket);
+    }
+    fn match_complete(&mut self, result: MatchResult) -> TournamentState {
+        if let Err(err) = self.csa.write(&result) {
+            eprintln!(
+                "Unable to write CSA record of game {}: {err}",
+                result.ticket.id + 1
+            );
+        }
+        self.inner.as_mut().match_complete(result)
+    }
+    fn print_interval_report(&self) {
+        self.inner.print_interval_report()
+    }
+    fn tournament_complete(&self) {
+        self.inner.tournament_complete()
+    }
+    fn expected_maximum_match_count(&self) -> Option<u64> {
+        self.inner.as_ref().expected_maximum_match_count()
+    }
+}
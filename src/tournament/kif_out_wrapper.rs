@@ -0,0 +1,51 @@
+use crate::{
+    cli, kif,
+    tournament::{MatchResult, MatchTicket, Tournament, TournamentState},
+};
+
+pub struct KifOutWrapper {
+    inner: Box<dyn Tournament>,
+    kif: kif::KifWriter,
+}
+
+impl KifOutWrapper {
+    pub fn new(
+        inner: Box<dyn Tournament>,
+        options: &cli::KifOutOptions,
+        meta: &cli::MetaDataOptions,
+        engine_options: Vec<cli::EngineOptions>,
+        engine_names: Vec<String>,
+    ) -> Result<KifOutWrapper, std::io::Error> {
+        Ok(KifOutWrapper {
+            inner,
+            kif: kif::KifWriter::new(options, meta, engine_options, engine_names)?,
+        })
+    }
+}
+
+impl Tournament for KifOutWrapper {
+    fn next(&mut self) -> Option<MatchTicket> {
+        self.inner.as_mut().next()
+    }
+    fn match_started(&mut self, ticket: MatchTicket) {
+        self.inner.as_mut().match_started(ticket);
+    }
+    fn match_complete(&mut self, result: MatchResult) -> TournamentState {
+        if let Err(err) = self.kif.write(&result) {
+            eprintln!(
+                "Unable to write KIF record of game {}: {err}",
+                result.ticket.id + 1
+            );
+        }
+        self.inner.as_mut().match_complete(result)
+    }
+    fn print_interval_report(&self) {
+        self.inner.print_interval_report()
+    }
+    fn tournament_complete(&self) {
+        self.inner.tournament_complete()
+    }
+    fn expected_maximum_match_count(&self) -> Option<u64> {
+        self.inner.as_ref().expected_maximum_match_count()
+    }
+}
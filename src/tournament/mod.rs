@@ -1,11 +1,15 @@
 use crate::{engine, shogi};
 use chrono::{DateTime, Utc};
 
+mod csa_out_wrapper;
+mod kif_out_wrapper;
 mod pgn_out_wrapper;
 mod reporter_wrapper;
 mod round_robin;
 mod stats_wrapper;
 
+pub use csa_out_wrapper::CsaOutWrapper;
+pub use kif_out_wrapper::KifOutWrapper;
 pub use pgn_out_wrapper::PgnOutWrapper;
 pub use reporter_wrapper::ReporterWrapper;
 pub use round_robin::RoundRobin;
@@ -15,6 +19,7 @@ pub use stats_wrapper::StatsWrapper;
 pub struct MatchTicket {
     pub id: u64,
     pub opening: shogi::Position,
+    pub opening_source: usize,
     pub engines: [usize; 2],
 }
 
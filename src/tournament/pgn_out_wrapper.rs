@@ -31,7 +31,12 @@ impl Tournament for PgnOutWrapper {
         self.inner.as_mut().match_started(ticket);
     }
     fn match_complete(&mut self, result: MatchResult) -> TournamentState {
-        self.pgn.write(&result).unwrap();
+        if let Err(err) = self.pgn.write(&result) {
+            eprintln!(
+                "Unable to write PGN record of game {}: {err}",
+                result.ticket.id + 1
+            );
+        }
         self.inner.as_mut().match_complete(result)
     }
     fn print_interval_report(&self) {
@@ -35,8 +35,8 @@ impl Tournament for ReporterWrapper {
             "Started game {}{} ({} vs {})",
             ticket.id + 1,
             self.format_of_max_string(),
-            &self.engine_names[ticket.engines[0]],
-            &self.engine_names[ticket.engines[1]]
+            self.engine_names[ticket.engines[0]],
+            self.engine_names[ticket.engines[1]]
         );
         self.inner.as_mut().match_started(ticket)
     }
@@ -45,8 +45,8 @@ impl Tournament for ReporterWrapper {
         println!(
             "Finished game {} ({} vs {}): {} {{{}}}",
             ticket.id + 1,
-            &self.engine_names[ticket.engines[0]],
-            &self.engine_names[ticket.engines[1]],
+            self.engine_names[ticket.engines[0]],
+            self.engine_names[ticket.engines[1]],
             match result.outcome.winner() {
                 Some(shogi::Color::Sente) => "1-0",
                 Some(shogi::Color::Gote) => "0-1",
@@ -14,6 +14,7 @@ pub struct RoundRobin {
     next_players: [usize; 2],
     total_matches: Option<u64>,
     players: usize,
+    handicap: bool,
     options: cli::CliOptions,
     openings: book::OpeningBook,
 }
@@ -26,6 +27,7 @@ impl RoundRobin {
             completed_matches: 0,
             next_players: [0, 1],
             players,
+            handicap: options.books.iter().any(|b| b.handicap.is_some()),
             total_matches: options
                 .games
                 .map(|g| pairings_count(players) * options.rounds * g),
@@ -38,10 +40,14 @@ impl RoundRobin {
 impl Tournament for RoundRobin {
     fn next(&mut self) -> Option<MatchTicket> {
         let id = self.match_index;
-        let opening = self.openings.current();
+        let opening = self.openings.current()?;
+        let opening_source = self.openings.source();
 
         let mut players = self.next_players;
-        if id % self.options.rounds % 2 == 1 {
+        if self.handicap {
+            // The engine listed first gives the handicap and plays Gote in every game
+            players.reverse();
+        } else if id % self.options.rounds % 2 == 1 {
             players.reverse();
         }
 
@@ -67,6 +73,7 @@ impl Tournament for RoundRobin {
             Some(MatchTicket {
                 id,
                 opening,
+                opening_source,
                 engines: players,
             })
         }
@@ -89,3 +96,53 @@ impl Tournament for RoundRobin {
         self.total_matches
     }
 }
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::shogi;
+    use rand::SeedableRng;
+
+    fn engines(book: cli::BookOptions) -> Vec<[usize; 2]> {
+        let options = cli::CliOptions {
+            engines: vec![cli::EngineOptions::default(); 3],
+            books: vec![book],
+            games: Some(1),
+            ..cli::CliOptions::default()
+        };
+        let mut rng = rand_chacha::ChaCha8Rng::seed_from_u64(0);
+        let openings =
+            book::OpeningBook::new(&options.books, options.variant, options.rules, &mut rng)
+                .unwrap();
+        let mut round_robin = RoundRobin::new(&options, openings);
+        std::iter::from_fn(|| round_robin.next())
+            .map(|ticket| ticket.engines)
+            .collect()
+    }
+
+    #[test]
+    fn handicap_colors() {
+        let file = std::env::temp_dir().join(format!("shogitest-rr-{}.epd", std::process::id()));
+        std::fs::write(&file, format!("{}\n", shogi::Position::default())).unwrap();
+        let book = cli::BookOptions {
+            file: file.to_string_lossy().to_string(),
+            ..cli::BookOptions::default()
+        };
+        // Engines swap sides within each pair
+        assert_eq!(
+            vec![[0, 1], [1, 0], [0, 2], [2, 0], [1, 2], [2, 1]],
+            engines(book)
+        );
+        std::fs::remove_file(&file).unwrap();
+
+        // The engine listed first gives the handicap, playing Gote in both games of a pair
+        let book = cli::BookOptions {
+            handicap: Some(shogi::Handicap::TwoPiece),
+            ..cli::BookOptions::default()
+        };
+        assert_eq!(
+            vec![[1, 0], [1, 0], [2, 0], [2, 0], [2, 1], [2, 1]],
+            engines(book)
+        );
+    }
+}
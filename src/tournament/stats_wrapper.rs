@@ -12,9 +12,11 @@ pub struct StatsWrapper {
     inner: Box<dyn Tournament>,
     engine_names: Vec<String>,
     engine_options: Vec<cli::EngineOptions>,
-    book_name: Option<String>,
+    book_names: Vec<String>,
     wdl_board: HashMap<(usize, usize), Wdl>,
     penta_board: HashMap<(usize, usize), Penta>,
+    // Results from Sente's point of view for each opening source
+    source_wdl: HashMap<usize, Wdl>,
     pending_pairing: HashMap<u64, ((usize, usize), Option<Color>)>,
     sprt: Option<SprtParameters>,
     match_ticket_count: u64,
@@ -27,7 +29,7 @@ impl StatsWrapper {
         inner: Box<dyn Tournament>,
         engine_names: Vec<String>,
         engine_options: Vec<cli::EngineOptions>,
-        book_name: Option<String>,
+        book_names: Vec<String>,
         sprt: Option<SprtParameters>,
     ) -> StatsWrapper {
         assert!(engine_names.len() == engine_options.len());
@@ -38,9 +40,10 @@ impl StatsWrapper {
             inner,
             engine_names,
             engine_options,
-            book_name,
+            book_names,
             wdl_board: HashMap::new(),
             penta_board: HashMap::new(),
+            source_wdl: HashMap::new(),
             pending_pairing: HashMap::new(),
             sprt,
             match_ticket_count: 0,
@@ -65,10 +68,16 @@ impl StatsWrapper {
     }
     fn add_penta_half(&mut self, match_id: u64, (a, b): (usize, usize), result1: Option<Color>) {
         let sibling = match_id ^ 1;
-        if let Some(((b2, a2), result2)) = self.pending_pairing.remove(&sibling) {
-            assert!(a == a2 && b == b2);
+        if let Some(((a2, b2), result2)) = self.pending_pairing.remove(&sibling) {
+            // Engines normally swap sides within a pair, but keep them in handicap games
+            let result2 = if (a, b) == (b2, a2) {
+                result2.map(|c| !c)
+            } else {
+                assert!(a == a2 && b == b2);
+                result2
+            };
 
-            let penta = match (result1, result2.map(|c| !c)) {
+            let penta = match (result1, result2) {
                 (Some(Color::Sente), Some(Color::Sente)) => Penta::ONE_WW,
                 (Some(Color::Sente), None) => Penta::ONE_WD,
                 (None, Some(Color::Sente)) => Penta::ONE_WD,
@@ -131,12 +140,14 @@ impl StatsWrapper {
                 .unwrap_or("null")
                 .to_string()
         });
-        let book = self
-            .book_name
-            .as_ref()
-            .and_then(|p| Path::new(p).file_name())
-            .map(|f| f.to_string_lossy().to_string())
-            .unwrap_or("null".to_string());
+        let book = if self.book_names.is_empty() {
+            "null".to_string()
+        } else {
+            (0..self.book_names.len())
+                .map(|i| self.book_display_name(i))
+                .collect::<Vec<_>>()
+                .join("+")
+        };
 
         println!(
             "Results of {} vs {} ({tc}, {threads}, {hash}, {book}):",
@@ -208,6 +219,29 @@ impl StatsWrapper {
             );
         }
     }
+    fn book_display_name(&self, source: usize) -> String {
+        let name = &self.book_names[source];
+        Path::new(name)
+            .file_name()
+            .map_or(name.clone(), |f| f.to_string_lossy().to_string())
+    }
+    pub fn print_source_stats(&self) {
+        for source in 0..self.book_names.len() {
+            let Some(wdl) = self.source_wdl.get(&source) else {
+                println!("Openings from {}: Games: 0", self.book_display_name(source));
+                continue;
+            };
+            println!(
+                "Openings from {}: Games: {}, Sente wins: {}, Draws: {}, Gote wins: {} (Sente score: {:.2}%)",
+                self.book_display_name(source),
+                wdl.game_count(),
+                wdl.w,
+                wdl.d,
+                wdl.l,
+                wdl.score() * 100.0
+            );
+        }
+    }
     fn next(&mut self) {
         self.match_ticket_count += 1;
     }
@@ -243,6 +277,16 @@ impl Tournament for StatsWrapper {
     fn match_complete(&mut self, result: MatchResult) -> TournamentState {
         let e = &result.ticket.engines;
         self.add_result(result.ticket.id, (e[0], e[1]), result.outcome.winner());
+        let wdl = match result.outcome.winner() {
+            Some(Color::Sente) => Wdl::ONE_WIN,
+            None => Wdl::ONE_DRAW,
+            Some(Color::Gote) => Wdl::ONE_LOSS,
+        };
+        let source_wdl = self
+            .source_wdl
+            .entry(result.ticket.opening_source)
+            .or_default();
+        *source_wdl = *source_wdl + wdl;
         self.match_complete();
         let state = self.inner.as_mut().match_complete(result);
         if self.match_completete_should_terminate() {
@@ -257,6 +301,9 @@ impl Tournament for StatsWrapper {
     }
     fn tournament_complete(&self) {
         self.print_stats();
+        if self.book_names.len() > 1 {
+            self.print_source_stats();
+        }
         self.inner.tournament_complete()
     }
     fn expected_maximum_match_count(&self) -> Option<u64> {
@@ -276,3 +323,68 @@ where
         format!("{first} - {second}")
     }
 }
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::{book, shogi, shogi::GameOutcome, tournament::RoundRobin};
+    use rand::SeedableRng;
+
+    #[test]
+    fn pentanomial_pairs() {
+        let options = cli::CliOptions {
+            engines: vec![cli::EngineOptions::default(); 2],
+            books: vec![cli::BookOptions {
+                handicap: Some(shogi::Handicap::TwoPiece),
+                ..cli::BookOptions::default()
+            }],
+            ..cli::CliOptions::default()
+        };
+        let mut rng = rand_chacha::ChaCha8Rng::seed_from_u64(0);
+        let openings =
+            book::OpeningBook::new(&options.books, options.variant, options.rules, &mut rng)
+                .unwrap();
+        let mut stats = StatsWrapper::new(
+            Box::new(RoundRobin::new(&options, openings)),
+            vec![String::from("a"), String::from("b")],
+            options.engines.clone(),
+            vec![],
+            None,
+        );
+
+        let results = [
+            // Handicap games keep the sides: engine 1 wins as Sente, then loses as Sente
+            ([1, 0], GameOutcome::Checkmated(Color::Gote)),
+            ([1, 0], GameOutcome::Resignation(Color::Sente)),
+            // Engine 1 wins both handicap games
+            ([1, 0], GameOutcome::Checkmated(Color::Gote)),
+            ([1, 0], GameOutcome::Resignation(Color::Gote)),
+            // Sente wins both games of a pair where the engines swap sides
+            ([0, 1], GameOutcome::Checkmated(Color::Gote)),
+            ([1, 0], GameOutcome::Checkmated(Color::Gote)),
+        ];
+        for (id, (engines, outcome)) in results.into_iter().enumerate() {
+            let result = MatchResult {
+                ticket: MatchTicket {
+                    id: id as u64,
+                    opening: shogi::Position::default(),
+                    opening_source: 0,
+                    engines,
+                },
+                game_start: chrono::Utc::now(),
+                outcome,
+                moves: vec![],
+            };
+            Tournament::match_complete(&mut stats, result);
+        }
+
+        let penta = |engine| {
+            let p = stats.all_penta_for(engine);
+            (p.ll, p.dl, p.dd, p.wl, p.wd, p.ww)
+        };
+        assert_eq!((0, 0, 0, 2, 0, 1), penta(1));
+        assert_eq!((1, 0, 0, 2, 0, 0), penta(0));
+        let wdl = stats.all_wdl_for(1);
+        assert_eq!((4, 0, 2), (wdl.w, wdl.d, wdl.l));
+    }
+}
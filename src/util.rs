@@ -9,3 +9,14 @@ where
     let file = File::open(filename)?;
     Ok(io::BufReader::new(file).lines())
 }
+
+// Inserts `number` before the extension, so "games.kif" becomes "games-1.kif".
+pub fn numbered_file_name(file: &str, number: u64) -> String {
+    let path = Path::new(file);
+    let stem = path.file_stem().unwrap_or_default().to_string_lossy();
+    let file_name = match path.extension() {
+        Some(ext) => format!("{stem}-{number}.{}", ext.to_string_lossy()),
+        None => format!("{stem}-{number}"),
+    };
+    path.with_file_name(file_name).to_string_lossy().to_string()
+}